@@ -10,9 +10,22 @@ fn main() {
         .with_sqlx_type(&["reservation.ReservationStatus"])
         .with_builder(&["reservation.ReservationQuery"])
         .with_builder_into_option("reservation.ReservationQuery", &["start", "end"])
+        .with_builder_option(
+            "reservation.ReservationQuery",
+            &["search", "pad", "note_regex", "min_party_size"],
+        )
         .with_builder_into(
             "reservation.ReservationQuery",
-            &["resource_id", "user_id", "status", "desc"],
+            &[
+                "resource_id",
+                "user_id",
+                "status",
+                "desc",
+                "created_by",
+                "duration_class",
+                "weekdays",
+                "weekday_tz",
+            ],
         )
         .field_attribute(
             "reservation.ReservationQuery.pagesize",
@@ -25,7 +38,7 @@ fn main() {
         .compile(&["protos/reservation.proto"], &["protos"])
         .unwrap();
 
-    Command::new("cargo").args(&["fmt"]).output().unwrap();
+    Command::new("cargo").args(["fmt"]).output().unwrap();
 
     println!("cargo:rerun-if-changed=protos/reservation.proto");
     println!("cargo:rerun-if-changed=build.rs");
@@ -36,6 +49,7 @@ trait BuilderExt {
     fn with_builder(self, paths: &[&str]) -> Self;
     fn with_builder_into(self, path: &str, fields: &[&str]) -> Self;
     fn with_builder_into_option(self, path: &str, fields: &[&str]) -> Self;
+    fn with_builder_option(self, path: &str, fields: &[&str]) -> Self;
 }
 
 impl BuilderExt for Builder {
@@ -68,4 +82,13 @@ impl BuilderExt for Builder {
             )
         })
     }
+
+    fn with_builder_option(self, path: &str, fields: &[&str]) -> Self {
+        fields.iter().fold(self, |acc, field| {
+            acc.field_attribute(
+                format!("{}.{}", path, field),
+                "#[builder(setter(into, strip_option), default)]",
+            )
+        })
+    }
 }
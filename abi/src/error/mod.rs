@@ -1,5 +1,6 @@
 mod conflict;
 
+use chrono::{DateTime, Duration, Utc};
 use sqlx::postgres::PgDatabaseError;
 
 pub use self::conflict::{ReservationConflict, ReservationConflictInfo, ReservationWindow};
@@ -12,6 +13,30 @@ pub enum Error {
     #[error("Conflict reservation")]
     ConflictReservation(ReservationConflictInfo),
 
+    #[error("Resource {} is unavailable from {} to {}", .window.rid, .window.start, .window.end)]
+    ResourceUnavailable { window: ReservationWindow },
+
+    #[error("Resource {resource_id} is fully booked (capacity {capacity})")]
+    CapacityExceeded { resource_id: String, capacity: i32 },
+
+    #[error("Invalid capacity: {0}")]
+    InvalidCapacity(i32),
+
+    #[error("Invalid location: ({lat}, {lon})")]
+    InvalidLocation { lat: f64, lon: f64 },
+
+    #[error("Slot length of {0} minutes doesn't divide a day evenly")]
+    InvalidSlotLength(u32),
+
+    #[error("Reservation must not start before {earliest}")]
+    InsufficientLeadTime { earliest: DateTime<Utc> },
+
+    #[error("Reservation rejected by policy: {0}")]
+    PolicyViolation(String),
+
+    #[error("Invalid encoded reservation: {0}")]
+    InvalidEncoding(String),
+
     #[error("No reservation found by the given condition")]
     NotFound,
 
@@ -21,16 +46,111 @@ pub enum Error {
     #[error("Invalid start or end time for the reservation")]
     InvalidTime,
 
+    #[error("Reservation {0} time is outside the supported date range")]
+    TimeOutOfRange(String),
+
+    #[error("Reservation ends ({end}) before it starts ({start})")]
+    EndBeforeStart {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+
     #[error("Invalid user id: {0}")]
     InvalidUserId(String),
 
     #[error("Invalid resource id: {0}")]
     InvalidResourceId(String),
 
+    #[error("Note is longer than {max} characters")]
+    NoteTooLong { max: usize },
+
+    #[error("Invalid reservation: {}", .0.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "))]
+    InvalidReservation(Vec<Error>),
+
+    #[error("Invalid currency code: {0}")]
+    InvalidCurrency(String),
+
+    #[error("Invalid party size: {0}")]
+    InvalidPartySize(i32),
+
+    #[error("Invalid units: {0}")]
+    InvalidUnits(i32),
+
+    #[error("User is already booked at that time by reservation {conflicting_id}")]
+    UserDoubleBooked { conflicting_id: String },
+
+    #[error("User already holds the maximum of {max} active reservations")]
+    UserQuotaExceeded { max: u32 },
+
+    #[error(
+        "User would have {} minutes booked, more than the allowed {}",
+        .would_be.num_minutes(),
+        .max.num_minutes()
+    )]
+    HoursQuotaExceeded { max: Duration, would_be: Duration },
+
+    #[error("Reservation was modified concurrently, its current version is {current}")]
+    VersionConflict { current: i64 },
+
+    #[error("Database is missing the {0} constraint, conflicts would go undetected")]
+    MissingConstraint(String),
+
+    #[error("Not authorized to perform this action")]
+    NotAuthorized,
+
+    #[error("Operation was cancelled")]
+    Cancelled,
+
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+
+    #[error("Invalid pricing rule: {0}")]
+    InvalidPricingRule(String),
+
     #[error("unknonwn error")]
     Unknown,
 }
 
+/// input problems detected before touching the database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    InvalidReservationId(String),
+    InvalidTime,
+    TimeOutOfRange(String),
+    EndBeforeStart {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+    InvalidUserId(String),
+    InvalidResourceId(String),
+    InvalidCurrency(String),
+    InvalidPartySize(i32),
+    InvalidUnits(i32),
+    NoteTooLong {
+        max: usize,
+    },
+}
+
+impl TryFrom<Error> for ValidationError {
+    type Error = Error;
+
+    fn try_from(e: Error) -> Result<Self, Self::Error> {
+        match e {
+            Error::InvalidReservationId(v) => Ok(Self::InvalidReservationId(v)),
+            Error::InvalidTime => Ok(Self::InvalidTime),
+            Error::TimeOutOfRange(v) => Ok(Self::TimeOutOfRange(v)),
+            Error::EndBeforeStart { start, end } => Ok(Self::EndBeforeStart { start, end }),
+            Error::InvalidUserId(v) => Ok(Self::InvalidUserId(v)),
+            Error::InvalidResourceId(v) => Ok(Self::InvalidResourceId(v)),
+            Error::InvalidCurrency(v) => Ok(Self::InvalidCurrency(v)),
+            Error::InvalidPartySize(v) => Ok(Self::InvalidPartySize(v)),
+            Error::InvalidUnits(v) => Ok(Self::InvalidUnits(v)),
+            Error::NoteTooLong { max } => Ok(Self::NoteTooLong { max }),
+            e => Err(e),
+        }
+    }
+}
+
 impl PartialEq for Error {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -39,8 +159,67 @@ impl PartialEq for Error {
             (Self::InvalidReservationId(v1), Self::InvalidReservationId(v2)) => v1 == v2,
             (Self::InvalidUserId(v1), Self::InvalidUserId(v2)) => v1 == v2,
             (Self::InvalidResourceId(v1), Self::InvalidResourceId(v2)) => v1 == v2,
+            (
+                Self::UserDoubleBooked { conflicting_id: v1 },
+                Self::UserDoubleBooked { conflicting_id: v2 },
+            ) => v1 == v2,
+            (
+                Self::ResourceUnavailable { window: v1 },
+                Self::ResourceUnavailable { window: v2 },
+            ) => v1 == v2,
+            (Self::InvalidQuery(v1), Self::InvalidQuery(v2)) => v1 == v2,
+            (Self::MissingConstraint(v1), Self::MissingConstraint(v2)) => v1 == v2,
+            (Self::VersionConflict { current: v1 }, Self::VersionConflict { current: v2 }) => {
+                v1 == v2
+            }
+            (Self::InvalidPricingRule(v1), Self::InvalidPricingRule(v2)) => v1 == v2,
+            (
+                Self::InsufficientLeadTime { earliest: v1 },
+                Self::InsufficientLeadTime { earliest: v2 },
+            ) => v1 == v2,
+            (Self::PolicyViolation(v1), Self::PolicyViolation(v2)) => v1 == v2,
+            (
+                Self::CapacityExceeded {
+                    resource_id: r1,
+                    capacity: c1,
+                },
+                Self::CapacityExceeded {
+                    resource_id: r2,
+                    capacity: c2,
+                },
+            ) => r1 == r2 && c1 == c2,
+            (Self::InvalidCapacity(v1), Self::InvalidCapacity(v2)) => v1 == v2,
+            (
+                Self::InvalidLocation { lat: a1, lon: o1 },
+                Self::InvalidLocation { lat: a2, lon: o2 },
+            ) => a1 == a2 && o1 == o2,
+            (Self::InvalidSlotLength(v1), Self::InvalidSlotLength(v2)) => v1 == v2,
+            (Self::InvalidEncoding(v1), Self::InvalidEncoding(v2)) => v1 == v2,
+            (Self::UserQuotaExceeded { max: v1 }, Self::UserQuotaExceeded { max: v2 }) => v1 == v2,
+            (Self::InvalidCurrency(v1), Self::InvalidCurrency(v2)) => v1 == v2,
+            (Self::InvalidPartySize(v1), Self::InvalidPartySize(v2)) => v1 == v2,
+            (Self::InvalidUnits(v1), Self::InvalidUnits(v2)) => v1 == v2,
+            (
+                Self::HoursQuotaExceeded {
+                    max: m1,
+                    would_be: w1,
+                },
+                Self::HoursQuotaExceeded {
+                    max: m2,
+                    would_be: w2,
+                },
+            ) => m1 == m2 && w1 == w2,
+            (Self::NoteTooLong { max: v1 }, Self::NoteTooLong { max: v2 }) => v1 == v2,
+            (Self::InvalidReservation(v1), Self::InvalidReservation(v2)) => v1 == v2,
             (Self::NotFound, Self::NotFound) => true,
+            (Self::Cancelled, Self::Cancelled) => true,
+            (Self::NotAuthorized, Self::NotAuthorized) => true,
             (Self::InvalidTime, Self::InvalidTime) => true,
+            (Self::TimeOutOfRange(v1), Self::TimeOutOfRange(v2)) => v1 == v2,
+            (
+                Self::EndBeforeStart { start: s1, end: e1 },
+                Self::EndBeforeStart { start: s2, end: e2 },
+            ) => s1 == s2 && e1 == e2,
             (Self::Unknown, Self::Unknown) => true,
             _ => false,
         }
@@ -56,6 +235,10 @@ impl From<sqlx::Error> for Error {
                     ("23P01", Some("rsvp"), Some("reservations")) => {
                         Error::ConflictReservation(err.detail().unwrap().parse().unwrap())
                     }
+                    // invalid_regular_expression
+                    ("2201B", _, _) => Error::InvalidQuery(err.message().to_string()),
+                    // invalid_parameter_value, e.g. an unknown time zone
+                    ("22023", _, _) => Error::InvalidQuery(err.message().to_string()),
                     _ => Error::DbError(sqlx::Error::Database(e)),
                 }
             }
@@ -64,3 +247,27 @@ impl From<sqlx::Error> for Error {
         }
     }
 }
+
+impl From<Error> for tonic::Status {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::DbError(_) | Error::MissingConstraint(_) | Error::Unknown => {
+                tonic::Status::internal(e.to_string())
+            }
+            Error::NotFound => tonic::Status::not_found(e.to_string()),
+            Error::Cancelled => tonic::Status::cancelled(e.to_string()),
+            Error::NotAuthorized => tonic::Status::permission_denied(e.to_string()),
+            Error::ConflictReservation(_)
+            | Error::ResourceUnavailable { .. }
+            | Error::UserDoubleBooked { .. } => tonic::Status::already_exists(e.to_string()),
+            Error::CapacityExceeded { .. }
+            | Error::UserQuotaExceeded { .. }
+            | Error::HoursQuotaExceeded { .. } => tonic::Status::resource_exhausted(e.to_string()),
+            Error::VersionConflict { .. } => tonic::Status::aborted(e.to_string()),
+            Error::InsufficientLeadTime { .. } | Error::PolicyViolation(_) => {
+                tonic::Status::failed_precondition(e.to_string())
+            }
+            _ => tonic::Status::invalid_argument(e.to_string()),
+        }
+    }
+}
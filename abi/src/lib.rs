@@ -3,8 +3,11 @@ mod pb;
 mod types;
 mod utils;
 
-pub use error::{Error, ReservationConflict, ReservationConflictInfo, ReservationWindow};
+pub use error::{
+    Error, ReservationConflict, ReservationConflictInfo, ReservationWindow, ValidationError,
+};
 pub use pb::*;
+pub use types::{find_batch_conflicts, MAX_NOTE_LEN};
 pub use utils::*;
 
 pub trait Validator {
@@ -18,4 +21,5 @@ pub enum RsvpStatus {
     Pending,
     Confirmed,
     Blocked,
+    Cancelled,
 }
@@ -16,6 +16,41 @@ pub struct Reservation {
     /// extra note
     #[prost(string, tag = "7")]
     pub note: ::prost::alloc::string::String,
+    /// agent who made the reservation on behalf of the user, empty if the user booked it
+    #[prost(string, tag = "8")]
+    pub created_by: ::prost::alloc::string::String,
+    /// server-assigned sequential booking reference, see `Reservation::reference_code`
+    #[prost(int64, tag = "9")]
+    pub reference: i64,
+    /// shared by all reservations booked together by `reserve_bundle`, empty otherwise
+    #[prost(string, tag = "10")]
+    pub bundle_id: ::prost::alloc::string::String,
+    /// when an unconfirmed hold lapses, unset for regular reservations
+    #[prost(message, optional, tag = "11")]
+    pub expires_at: ::core::option::Option<::prost_types::Timestamp>,
+    /// price in the smallest unit of `currency`
+    #[prost(int64, optional, tag = "12")]
+    pub amount_cents: ::core::option::Option<i64>,
+    /// ISO 4217 code, e.g. "USD"
+    #[prost(string, optional, tag = "13")]
+    pub currency: ::core::option::Option<::prost::alloc::string::String>,
+    /// bumped on every write, see `update_if_version`
+    #[prost(int64, tag = "14")]
+    pub version: i64,
+    /// number of guests, unset if not asked
+    #[prost(int32, optional, tag = "15")]
+    pub party_size: ::core::option::Option<i32>,
+    /// e.g. check-in instructions, one request per entry
+    #[prost(string, repeated, tag = "16")]
+    pub special_requests: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// the only user allowed to confirm the reservation, see `approve`. Empty if anyone may
+    #[prost(string, tag = "17")]
+    pub approver_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "ApprovalStatus", tag = "18")]
+    pub approval_status: i32,
+    /// units of the resource's capacity taken, e.g. seats. Unset means 1
+    #[prost(int32, optional, tag = "19")]
+    pub units: ::core::option::Option<i32>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ReserveRequest {
@@ -96,6 +131,39 @@ pub struct ReservationQuery {
     #[prost(bool, tag = "8")]
     #[builder(setter(into), default)]
     pub desc: bool,
+    /// free-text search across user id, resource id and note
+    #[prost(string, optional, tag = "9")]
+    #[builder(setter(into, strip_option), default)]
+    pub search: ::core::option::Option<::prost::alloc::string::String>,
+    /// agent who made the reservation
+    #[prost(string, tag = "10")]
+    #[builder(setter(into), default)]
+    pub created_by: ::prost::alloc::string::String,
+    /// widen the [start, end) window by pad on both sides
+    #[prost(message, optional, tag = "11")]
+    #[builder(setter(into, strip_option), default)]
+    pub pad: ::core::option::Option<::prost_types::Duration>,
+    /// only return reservations whose note matches this (POSIX) regular expression
+    #[prost(string, optional, tag = "12")]
+    #[builder(setter(into, strip_option), default)]
+    pub note_regex: ::core::option::Option<::prost::alloc::string::String>,
+    /// use duration class to filter result. If UNSPECIFIED, return all durations
+    #[prost(enumeration = "DurationClass", tag = "13")]
+    #[builder(setter(into), default)]
+    pub duration_class: i32,
+    /// only return reservations starting on these weekdays (0 is Sunday) in weekday_tz.
+    /// If empty, return all weekdays
+    #[prost(int32, repeated, tag = "14")]
+    #[builder(setter(into), default)]
+    pub weekdays: ::prost::alloc::vec::Vec<i32>,
+    /// IANA time zone the weekdays are evaluated in, UTC if empty
+    #[prost(string, tag = "15")]
+    #[builder(setter(into), default)]
+    pub weekday_tz: ::prost::alloc::string::String,
+    /// only return reservations for at least this many guests
+    #[prost(int32, optional, tag = "16")]
+    #[builder(setter(into, strip_option), default)]
+    pub min_party_size: ::core::option::Option<i32>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct QueryRequest {
@@ -118,6 +186,7 @@ pub enum ReservationStatus {
     Pending = 1,
     Confirmed = 2,
     Blocked = 3,
+    Cancelled = 4,
 }
 impl ReservationStatus {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -130,6 +199,7 @@ impl ReservationStatus {
             ReservationStatus::Pending => "RESERVATION_STATUS_PENDING",
             ReservationStatus::Confirmed => "RESERVATION_STATUS_CONFIRMED",
             ReservationStatus::Blocked => "RESERVATION_STATUS_BLOCKED",
+            ReservationStatus::Cancelled => "RESERVATION_STATUS_CANCELLED",
         }
     }
 }
@@ -155,6 +225,56 @@ impl ReservationUpdateType {
         }
     }
 }
+/// coarse classification of a reservation by its length
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum DurationClass {
+    Unspecified = 0,
+    /// shorter than an hour
+    SubHour = 1,
+    /// an hour up to a day
+    Hourly = 2,
+    /// a day up to two days
+    Daily = 3,
+    /// two days or longer
+    Multiday = 4,
+}
+impl DurationClass {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            DurationClass::Unspecified => "DURATION_CLASS_UNSPECIFIED",
+            DurationClass::SubHour => "DURATION_CLASS_SUB_HOUR",
+            DurationClass::Hourly => "DURATION_CLASS_HOURLY",
+            DurationClass::Daily => "DURATION_CLASS_DAILY",
+            DurationClass::Multiday => "DURATION_CLASS_MULTIDAY",
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ApprovalStatus {
+    /// no approver assigned
+    NotRequired = 0,
+    Pending = 1,
+    Approved = 2,
+}
+impl ApprovalStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ApprovalStatus::NotRequired => "APPROVAL_STATUS_NOT_REQUIRED",
+            ApprovalStatus::Pending => "APPROVAL_STATUS_PENDING",
+            ApprovalStatus::Approved => "APPROVAL_STATUS_APPROVED",
+        }
+    }
+}
 /// Generated client implementations.
 pub mod reservation_service_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -10,6 +10,13 @@ mod reservation;
 mod reservation_query;
 mod reservation_status;
 
+pub use reservation::{find_batch_conflicts, MAX_NOTE_LEN};
+
+/// 0001-01-01T00:00:00Z, the earliest accepted reservation time
+const MIN_SECONDS: i64 = -62_135_596_800;
+/// 9999-12-31T23:59:59Z, the latest accepted reservation time
+const MAX_SECONDS: i64 = 253_402_300_799;
+
 pub fn validate_range(start: Option<&Timestamp>, end: Option<&Timestamp>) -> Result<(), Error> {
     if start.is_none() || end.is_none() {
         return Err(Error::InvalidTime);
@@ -18,7 +25,21 @@ pub fn validate_range(start: Option<&Timestamp>, end: Option<&Timestamp>) -> Res
     let start = start.as_ref().unwrap();
     let end = end.as_ref().unwrap();
 
-    if start.seconds >= end.seconds {
+    // checked first, converting times outside chrono's range panics
+    for (field, ts) in [("start", start), ("end", end)] {
+        if !(MIN_SECONDS..=MAX_SECONDS).contains(&ts.seconds) {
+            return Err(Error::TimeOutOfRange(field.to_string()));
+        }
+    }
+
+    if start.seconds > end.seconds {
+        return Err(Error::EndBeforeStart {
+            start: convert_to_utc_time(start),
+            end: convert_to_utc_time(end),
+        });
+    }
+
+    if start.seconds == end.seconds {
         return Err(Error::InvalidTime);
     }
 
@@ -34,3 +55,49 @@ pub fn get_timespan(start: Option<&Timestamp>, end: Option<&Timestamp>) -> PgRan
         end: Bound::Excluded(end),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_range_should_reject_swapped_window() {
+        let start: Timestamp = "2022-12-28T12:00:00-0700".parse().unwrap();
+        let end: Timestamp = "2022-12-25T15:00:00-0700".parse().unwrap();
+
+        let err = validate_range(Some(&start), Some(&end)).unwrap_err();
+
+        assert_eq!(
+            err,
+            Error::EndBeforeStart {
+                start: "2022-12-28T19:00:00Z".parse().unwrap(),
+                end: "2022-12-25T22:00:00Z".parse().unwrap(),
+            }
+        );
+    }
+
+    #[test]
+    fn validate_range_should_reject_far_future_time() {
+        let start: Timestamp = "2022-12-25T15:00:00-0700".parse().unwrap();
+        // roughly the year 292278994
+        let end = Timestamp {
+            seconds: 9_223_372_036_854_775,
+            nanos: 0,
+        };
+
+        let err = validate_range(Some(&start), Some(&end)).unwrap_err();
+        assert_eq!(err, Error::TimeOutOfRange("end".to_string()));
+
+        let err = validate_range(Some(&end), Some(&start)).unwrap_err();
+        assert_eq!(err, Error::TimeOutOfRange("start".to_string()));
+    }
+
+    #[test]
+    fn validate_range_should_reject_missing_end() {
+        let start: Timestamp = "2022-12-25T15:00:00-0700".parse().unwrap();
+
+        let err = validate_range(Some(&start), None).unwrap_err();
+
+        assert_eq!(err, Error::InvalidTime);
+    }
+}
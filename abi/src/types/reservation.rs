@@ -1,16 +1,27 @@
 use std::ops::Bound;
 
-use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use prost::Message;
+use prost_types::Timestamp;
 use sqlx::{
     postgres::{types::PgRange, PgRow},
     types::Uuid,
     FromRow, Row,
 };
 
-use crate::{utils::to_timestamp, Error, Reservation, ReservationStatus, RsvpStatus, Validator};
+use crate::{
+    utils::{convert_to_timestamp, to_datetime, to_timestamp},
+    ApprovalStatus, Error, Reservation, ReservationStatus, RsvpStatus, Validator,
+};
 
 use super::{get_timespan, validate_range};
 
+/// maximum note length, in characters
+pub const MAX_NOTE_LEN: usize = 1024;
+
+/// leading byte of `Reservation::encode_to_vec`, bumped on incompatible changes
+const ENCODING_VERSION: u8 = 1;
+
 impl Reservation {
     pub fn new_pending(
         uid: impl Into<String>,
@@ -27,27 +38,173 @@ impl Reservation {
             end_time: Some(to_timestamp(end)),
             start_time: Some(to_timestamp(start)),
             note: note.into(),
+            created_by: "".to_string(),
+            reference: 0,
+            bundle_id: "".to_string(),
+            expires_at: None,
+            amount_cents: None,
+            currency: None,
+            version: 0,
+            party_size: None,
+            special_requests: vec![],
+            approver_id: "".to_string(),
+            approval_status: ApprovalStatus::NotRequired as i32,
+            units: None,
+        }
+    }
+
+    /// create a pending reservation from wall-clock times without offset, interpreted in `tz`
+    pub fn new_pending_in_tz(
+        uid: impl Into<String>,
+        rid: impl Into<String>,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+        tz: FixedOffset,
+        note: impl Into<String>,
+    ) -> Self {
+        // a fixed offset never yields an ambiguous or missing local time
+        let start = tz.from_local_datetime(&start).unwrap();
+        let end = tz.from_local_datetime(&end).unwrap();
+        Self::new_pending(uid, rid, start, end, note)
+    }
+
+    /// versioned protobuf encoding, e.g. for caching
+    pub fn encode_to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(1 + self.encoded_len());
+        buf.push(ENCODING_VERSION);
+        // writing into a Vec can't run out of capacity
+        self.encode(&mut buf).unwrap();
+        buf
+    }
+
+    /// decode bytes produced by `encode_to_vec`
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        match bytes.split_first() {
+            Some((&ENCODING_VERSION, rest)) => {
+                <Self as Message>::decode(rest).map_err(|e| Error::InvalidEncoding(e.to_string()))
+            }
+            Some((v, _)) => Err(Error::InvalidEncoding(format!("unknown version {}", v))),
+            None => Err(Error::InvalidEncoding("empty input".to_string())),
         }
     }
 
+    /// human-friendly booking reference, e.g. `RSVP-000123`
+    pub fn reference_code(&self) -> String {
+        format!("RSVP-{:06}", self.reference)
+    }
+
     pub fn get_timespan(&self) -> PgRange<DateTime<Utc>> {
         get_timespan(self.start_time.as_ref(), self.end_time.as_ref())
     }
+
+    /// whether both reservations are on the same resource and their half-open windows intersect
+    pub fn overlaps(&self, other: &Self) -> bool {
+        if self.resource_id != other.resource_id {
+            return false;
+        }
+
+        match (self.window(), other.window()) {
+            (Some((start1, end1)), Some((start2, end2))) => start1 < end2 && start2 < end1,
+            _ => false,
+        }
+    }
+
+    fn window(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let start = to_datetime(self.start_time.as_ref()).ok()?;
+        let end = to_datetime(self.end_time.as_ref()).ok()?;
+        Some((start, end))
+    }
+
+    /// compare two reservations as the same logical booking.
+    ///
+    /// The derived `PartialEq` on `Reservation` compares the raw `Timestamp` encoding, so the
+    /// same instant stored as e.g. `{ seconds: 9, nanos: 1_000_000_000 }` and `{ seconds: 10 }`
+    /// is not equal. This compares the normalized instants instead.
+    pub fn same_booking(&self, other: &Self) -> bool {
+        self.id == other.id
+            && self.resource_id == other.resource_id
+            && self.user_id == other.user_id
+            && self.status == other.status
+            && self.note == other.note
+            && normalized(self.start_time.as_ref()) == normalized(other.start_time.as_ref())
+            && normalized(self.end_time.as_ref()) == normalized(other.end_time.as_ref())
+    }
 }
 
-impl Validator for Reservation {
-    fn validate(&self) -> Result<(), Error> {
+/// find index pairs of staged reservations that would conflict with each other on the same resource.
+/// Cancelled reservations never conflict.
+pub fn find_batch_conflicts(rsvps: &[Reservation]) -> Vec<(usize, usize)> {
+    let active = |rsvp: &Reservation| rsvp.status != ReservationStatus::Cancelled as i32;
+
+    let mut conflicts = Vec::new();
+    for (i, a) in rsvps.iter().enumerate().filter(|(_, r)| active(r)) {
+        for (j, b) in rsvps.iter().enumerate().skip(i + 1) {
+            if active(b) && a.overlaps(b) {
+                conflicts.push((i, j));
+            }
+        }
+    }
+    conflicts
+}
+
+fn normalized(ts: Option<&Timestamp>) -> Option<Timestamp> {
+    ts.map(|ts| {
+        let mut ts = ts.clone();
+        ts.normalize();
+        ts
+    })
+}
+
+impl Reservation {
+    /// like `validate`, but report every failing rule instead of only the first
+    pub fn validate_all(&self) -> Result<(), Vec<Error>> {
+        let mut errors = vec![];
+
         if self.user_id.is_empty() {
-            return Err(Error::InvalidUserId("".into()));
+            errors.push(Error::InvalidUserId("".into()));
         }
 
         if self.resource_id.is_empty() {
-            return Err(Error::InvalidResourceId("".into()));
+            errors.push(Error::InvalidResourceId("".into()));
         }
 
-        validate_range(self.start_time.as_ref(), self.end_time.as_ref())?;
+        if let Err(e) = validate_range(self.start_time.as_ref(), self.end_time.as_ref()) {
+            errors.push(e);
+        }
+
+        if self.note.chars().count() > MAX_NOTE_LEN {
+            errors.push(Error::NoteTooLong { max: MAX_NOTE_LEN });
+        }
+
+        if let Some(currency) = &self.currency {
+            if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_uppercase()) {
+                errors.push(Error::InvalidCurrency(currency.clone()));
+            }
+        }
+
+        if let Some(size) = self.party_size {
+            if size < 1 {
+                errors.push(Error::InvalidPartySize(size));
+            }
+        }
+
+        if let Some(units) = self.units {
+            if units < 1 {
+                errors.push(Error::InvalidUnits(units));
+            }
+        }
 
-        Ok(())
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Validator for Reservation {
+    fn validate(&self) -> Result<(), Error> {
+        self.validate_all().map_err(|mut errors| errors.remove(0))
     }
 }
 
@@ -74,6 +231,30 @@ impl FromRow<'_, PgRow> for Reservation {
             end_time: Some(end),
             start_time: Some(start),
             note: row.get("note"),
+            created_by: row
+                .get::<Option<String>, _>("created_by")
+                .unwrap_or_default(),
+            reference: row.get("reference"),
+            bundle_id: row
+                .get::<Option<Uuid>, _>("bundle_id")
+                .map(|id| id.to_string())
+                .unwrap_or_default(),
+            expires_at: row
+                .get::<Option<DateTime<Utc>>, _>("expires_at")
+                .map(convert_to_timestamp),
+            amount_cents: row.get("amount_cents"),
+            currency: row.get("currency"),
+            version: row.get("version"),
+            party_size: row.get("party_size"),
+            special_requests: row.get("special_requests"),
+            approver_id: row
+                .get::<Option<String>, _>("approver_id")
+                .unwrap_or_default(),
+            approval_status: row
+                .get::<String, _>("approval_status")
+                .parse::<ApprovalStatus>()
+                .unwrap_or_default() as i32,
+            units: row.get("units"),
         })
     }
 }
@@ -96,3 +277,146 @@ impl<T> From<PgRange<T>> for NaiveRange<T> {
         Self { start, end }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode_should_round_trip() {
+        let mut rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "hello",
+        );
+        rsvp.id = "d3b07384-d9a7-4e3b-8a1e-0f6e1b2a3c4d".to_string();
+        rsvp.reference = 123;
+
+        let bytes = rsvp.encode_to_vec();
+        assert_eq!(Reservation::decode(&bytes).unwrap(), rsvp);
+
+        assert!(matches!(
+            Reservation::decode(&[]),
+            Err(Error::InvalidEncoding(_))
+        ));
+        let mut bytes = bytes;
+        bytes[0] = ENCODING_VERSION + 1;
+        assert!(matches!(
+            Reservation::decode(&bytes),
+            Err(Error::InvalidEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn validate_should_reject_invalid_currency() {
+        let mut rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        rsvp.currency = Some("USD".to_string());
+        assert!(rsvp.validate().is_ok());
+
+        for code in ["usd", "US", "USDT", "U$D"] {
+            rsvp.currency = Some(code.to_string());
+            assert_eq!(
+                rsvp.validate().unwrap_err(),
+                Error::InvalidCurrency(code.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn validate_all_should_report_every_failing_rule() {
+        let mut rsvp = Reservation::new_pending(
+            "",
+            "1021",
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "x".repeat(MAX_NOTE_LEN + 1),
+        );
+        rsvp.currency = Some("USD".to_string());
+
+        let errors = rsvp.validate_all().unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                Error::InvalidUserId("".into()),
+                Error::EndBeforeStart {
+                    start: "2022-12-28T19:00:00Z".parse().unwrap(),
+                    end: "2022-12-25T22:00:00Z".parse().unwrap(),
+                },
+                Error::NoteTooLong { max: MAX_NOTE_LEN },
+            ]
+        );
+        assert_eq!(rsvp.validate().unwrap_err(), errors[0]);
+    }
+
+    #[test]
+    fn same_booking_should_ignore_timestamp_encoding() {
+        let rsvp1 = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "hello",
+        );
+        let mut rsvp2 = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T22:00:00+0000".parse().unwrap(),
+            "2022-12-28T19:00:00+0000".parse().unwrap(),
+            "hello",
+        );
+        let start = rsvp2.start_time.as_mut().unwrap();
+        start.seconds -= 1;
+        start.nanos += 1_000_000_000;
+
+        assert_ne!(rsvp1, rsvp2);
+        assert!(rsvp1.same_booking(&rsvp2));
+
+        rsvp2.end_time.as_mut().unwrap().seconds += 1;
+        assert!(!rsvp1.same_booking(&rsvp2));
+    }
+
+    #[test]
+    fn find_batch_conflicts_should_report_overlapping_pairs() {
+        let rsvp = |rid: &str, start: &str, end: &str| {
+            Reservation::new_pending(
+                "tyrId",
+                rid,
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            )
+        };
+        let batch = vec![
+            rsvp(
+                "room-1",
+                "2022-12-25T10:00:00-0700",
+                "2022-12-25T12:00:00-0700",
+            ),
+            rsvp(
+                "room-1",
+                "2022-12-25T12:00:00-0700",
+                "2022-12-25T14:00:00-0700",
+            ),
+            rsvp(
+                "room-2",
+                "2022-12-25T10:00:00-0700",
+                "2022-12-25T12:00:00-0700",
+            ),
+            rsvp(
+                "room-1",
+                "2022-12-25T13:00:00-0700",
+                "2022-12-25T15:00:00-0700",
+            ),
+        ];
+
+        assert_eq!(find_batch_conflicts(&batch), vec![(1, 3)]);
+    }
+}
@@ -1,13 +1,39 @@
-use chrono::{DateTime, Utc};
+use std::ops::Bound;
+
+use chrono::{DateTime, Duration, Utc, Weekday};
+use regex::Regex;
 use sqlx::postgres::types::PgRange;
 
-use crate::{Error, ReservationQuery, Validator};
+use crate::{Error, ReservationQuery, ReservationQueryBuilder, Validator};
 
 use super::{get_timespan, validate_range};
 
 impl ReservationQuery {
     pub fn timespan(&self) -> PgRange<DateTime<Utc>> {
-        get_timespan(self.start.as_ref(), self.end.as_ref())
+        let mut range = get_timespan(self.start.as_ref(), self.end.as_ref());
+        let pad = self
+            .pad
+            .as_ref()
+            .map(|pad| Duration::seconds(pad.seconds.max(0)))
+            .unwrap_or_else(Duration::zero);
+
+        if let (Bound::Included(start), Bound::Excluded(end)) = (&mut range.start, &mut range.end) {
+            *start -= pad;
+            *end += pad;
+        }
+        range
+    }
+}
+
+impl ReservationQueryBuilder {
+    /// only match reservations starting on `days` in the IANA time zone `tz`
+    pub fn on_weekdays(&mut self, days: &[Weekday], tz: impl Into<String>) -> &mut Self {
+        self.weekdays(
+            days.iter()
+                .map(|d| d.num_days_from_sunday() as i32)
+                .collect::<Vec<_>>(),
+        )
+        .weekday_tz(tz)
     }
 }
 
@@ -15,6 +41,15 @@ impl Validator for ReservationQuery {
     fn validate(&self) -> Result<(), Error> {
         validate_range(self.start.as_ref(), self.end.as_ref())?;
 
+        if let Some(pattern) = self.note_regex.as_deref() {
+            Regex::new(pattern)
+                .map_err(|e| Error::InvalidQuery(format!("invalid note regex: {}", e)))?;
+        }
+
+        if let Some(day) = self.weekdays.iter().find(|d| !(0..=6).contains(*d)) {
+            return Err(Error::InvalidQuery(format!("invalid weekday: {}", day)));
+        }
+
         Ok(())
     }
 }
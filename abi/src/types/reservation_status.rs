@@ -1,4 +1,6 @@
-use crate::{ReservationStatus, RsvpStatus};
+use std::str::FromStr;
+
+use crate::{ApprovalStatus, Error, ReservationStatus, RsvpStatus};
 
 impl From<RsvpStatus> for ReservationStatus {
     fn from(r: RsvpStatus) -> Self {
@@ -7,6 +9,7 @@ impl From<RsvpStatus> for ReservationStatus {
             RsvpStatus::Pending => Self::Pending,
             RsvpStatus::Confirmed => Self::Confirmed,
             RsvpStatus::Blocked => Self::Blocked,
+            RsvpStatus::Cancelled => Self::Cancelled,
         }
     }
 }
@@ -18,6 +21,30 @@ impl std::fmt::Display for ReservationStatus {
             ReservationStatus::Pending => write!(f, "pending"),
             ReservationStatus::Blocked => write!(f, "blocked"),
             ReservationStatus::Confirmed => write!(f, "confirmed"),
+            ReservationStatus::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::fmt::Display for ApprovalStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApprovalStatus::NotRequired => write!(f, "not_required"),
+            ApprovalStatus::Pending => write!(f, "pending"),
+            ApprovalStatus::Approved => write!(f, "approved"),
+        }
+    }
+}
+
+impl FromStr for ApprovalStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "not_required" => Ok(Self::NotRequired),
+            "pending" => Ok(Self::Pending),
+            "approved" => Ok(Self::Approved),
+            _ => Err(Error::Unknown),
         }
     }
 }
@@ -0,0 +1,3 @@
+-- Add down migration script here
+-- enum values can't be dropped, 'cancelled' stays in rsvp.reservation_status
+ALTER TABLE rsvp.reservations DROP COLUMN cancelled_at;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+ALTER TYPE rsvp.reservation_status ADD VALUE 'cancelled';
+ALTER TABLE rsvp.reservations ADD COLUMN cancelled_at TIMESTAMPTZ;
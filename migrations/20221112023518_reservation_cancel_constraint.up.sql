@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- cancelled reservations no longer hold their window
+ALTER TABLE rsvp.reservations DROP CONSTRAINT reservations_conflict;
+ALTER TABLE rsvp.reservations ADD CONSTRAINT reservations_conflict
+    EXCLUDE USING gist(resource_id WITH =, timespan WITH &&) WHERE (status <> 'cancelled');
@@ -0,0 +1,56 @@
+-- Add up migration script here
+DROP FUNCTION rsvp.query(text, text, TSTZRANGE, rsvp.reservation_status, integer, bool, integer);
+
+CREATE OR REPLACE FUNCTION rsvp.query(
+    uid text, rid text, during TSTZRANGE,
+    status rsvp.reservation_status,
+    page integer DEFAULT 1,
+    is_desc bool DEFAULT FALSE,
+    page_size integer DEFAULT 10,
+    search text DEFAULT NULL
+) RETURNS TABLE (LIKE rsvp.reservations)
+AS $$
+
+DECLARE
+    _sql text;
+    _pattern text;
+BEGIN
+
+    IF page_size <= 0 THEN
+        page_size := 10;
+    END IF;
+
+    IF page < 1 THEN
+        page := 1;
+    END IF;
+
+    -- escape LIKE wildcards so the search term is matched literally
+    _pattern := '%' || replace(replace(replace(search, '\', '\\'), '%', '\%'), '_', '\_') || '%';
+
+    _sql := format(
+        'SELECT * FROM rsvp.reservations WHERE %L @> timespan AND status = %L AND %s AND %s ORDER BY lower(timespan) %s LIMIT %L::integer OFFSET %L::integer;',
+        during,
+        status,
+        CASE
+            WHEN uid IS NULL AND rid IS NULL THEN 'TRUE'
+            WHEN uid IS NULL THEN 'resource_id = ' || quote_literal(rid)
+            WHEN rid IS NULL THEN 'user_id = ' || quote_literal(uid)
+            ELSE 'user_id = ' || quote_literal(uid) || 'AND resource_id = ' || quote_literal(rid)
+        END,
+        CASE
+            WHEN search IS NULL THEN 'TRUE'
+            ELSE format('(user_id ILIKE %1$L OR resource_id ILIKE %1$L OR note ILIKE %1$L)', _pattern)
+        END,
+        CASE
+            WHEN is_desc THEN 'DESC'
+            ELSE 'ASC'
+        END,
+        page_size,
+        (page - 1) * page_size
+    );
+
+    -- RAISE NOTICE '%', _sql;
+
+    RETURN QUERY EXECUTE _sql;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE rsvp.blackouts (
+    id SERIAL NOT NULL,
+    resource_id VARCHAR(64) NOT NULL,
+    timespan TSTZRANGE NOT NULL,
+
+    CONSTRAINT blackouts_pkey PRIMARY KEY (id)
+);
+
+CREATE INDEX blackouts_resource_id_timespan_idx ON rsvp.blackouts USING gist (resource_id, timespan);
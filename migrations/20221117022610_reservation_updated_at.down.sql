@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TRIGGER reservations_touch ON rsvp.reservations;
+DROP FUNCTION rsvp.reservations_touch();
+ALTER TABLE rsvp.reservations DROP COLUMN updated_at;
@@ -0,0 +1,13 @@
+-- Add up migration script here
+ALTER TABLE rsvp.reservations ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT clock_timestamp();
+
+CREATE OR REPLACE FUNCTION rsvp.reservations_touch() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at := clock_timestamp();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER reservations_touch
+    BEFORE UPDATE ON rsvp.reservations
+    FOR EACH ROW EXECUTE PROCEDURE rsvp.reservations_touch();
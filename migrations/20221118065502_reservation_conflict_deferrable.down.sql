@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE rsvp.reservations DROP CONSTRAINT reservations_conflict;
+ALTER TABLE rsvp.reservations ADD CONSTRAINT reservations_conflict
+    EXCLUDE USING gist(resource_id WITH =, timespan WITH &&) WHERE (status <> 'cancelled');
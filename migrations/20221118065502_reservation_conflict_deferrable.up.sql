@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- allow multi-row moves (e.g. swapping two slots) to check conflicts at commit
+ALTER TABLE rsvp.reservations DROP CONSTRAINT reservations_conflict;
+ALTER TABLE rsvp.reservations ADD CONSTRAINT reservations_conflict
+    EXCLUDE USING gist(resource_id WITH =, timespan WITH &&) WHERE (status <> 'cancelled')
+    DEFERRABLE INITIALLY IMMEDIATE;
@@ -0,0 +1,69 @@
+-- Add up migration script here
+DROP FUNCTION rsvp.query(text, text, TSTZRANGE, rsvp.reservation_status, integer, bool, integer, text, text);
+
+CREATE OR REPLACE FUNCTION rsvp.query(
+    uid text, rid text, during TSTZRANGE,
+    status rsvp.reservation_status,
+    page integer DEFAULT 1,
+    is_desc bool DEFAULT FALSE,
+    page_size integer DEFAULT 10,
+    search text DEFAULT NULL,
+    agent text DEFAULT NULL,
+    note_pattern text DEFAULT NULL
+) RETURNS TABLE (LIKE rsvp.reservations)
+AS $$
+
+DECLARE
+    _sql text;
+    _where text;
+    _pattern text;
+BEGIN
+
+    IF page_size <= 0 THEN
+        page_size := 10;
+    END IF;
+
+    IF page < 1 THEN
+        page := 1;
+    END IF;
+
+    _where := format('%L @> timespan AND status = %L', during, status);
+
+    IF uid IS NOT NULL THEN
+        _where := _where || ' AND user_id = ' || quote_literal(uid);
+    END IF;
+
+    IF rid IS NOT NULL THEN
+        _where := _where || ' AND resource_id = ' || quote_literal(rid);
+    END IF;
+
+    IF agent IS NOT NULL THEN
+        _where := _where || ' AND created_by = ' || quote_literal(agent);
+    END IF;
+
+    IF note_pattern IS NOT NULL THEN
+        _where := _where || ' AND note ~ ' || quote_literal(note_pattern);
+    END IF;
+
+    IF search IS NOT NULL THEN
+        -- escape LIKE wildcards so the search term is matched literally
+        _pattern := '%' || replace(replace(replace(search, '\', '\\'), '%', '\%'), '_', '\_') || '%';
+        _where := _where || format(' AND (user_id ILIKE %1$L OR resource_id ILIKE %1$L OR note ILIKE %1$L)', _pattern);
+    END IF;
+
+    _sql := format(
+        'SELECT * FROM rsvp.reservations WHERE %s ORDER BY lower(timespan) %s LIMIT %L::integer OFFSET %L::integer;',
+        _where,
+        CASE
+            WHEN is_desc THEN 'DESC'
+            ELSE 'ASC'
+        END,
+        page_size,
+        (page - 1) * page_size
+    );
+
+    -- RAISE NOTICE '%', _sql;
+
+    RETURN QUERY EXECUTE _sql;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+CREATE SEQUENCE rsvp.reservation_reference_seq;
+
+ALTER TABLE rsvp.reservations
+    ADD COLUMN reference BIGINT NOT NULL DEFAULT nextval('rsvp.reservation_reference_seq') UNIQUE;
+
+ALTER SEQUENCE rsvp.reservation_reference_seq OWNED BY rsvp.reservations.reference;
@@ -0,0 +1,16 @@
+-- Add up migration script here
+CREATE TABLE rsvp.resources (
+    id VARCHAR(64) NOT NULL,
+    capacity INTEGER NOT NULL,
+
+    CONSTRAINT resources_pkey PRIMARY KEY (id),
+    CONSTRAINT resources_capacity CHECK (capacity > 0)
+);
+
+-- reservations on resources with capacity > 1 may overlap, their count is checked on insert
+ALTER TABLE rsvp.reservations ADD COLUMN shared BOOLEAN NOT NULL DEFAULT false;
+
+ALTER TABLE rsvp.reservations DROP CONSTRAINT reservations_conflict;
+ALTER TABLE rsvp.reservations ADD CONSTRAINT reservations_conflict
+    EXCLUDE USING gist(resource_id WITH =, timespan WITH &&) WHERE (status <> 'cancelled' AND NOT shared)
+    DEFERRABLE INITIALLY IMMEDIATE;
@@ -0,0 +1,25 @@
+-- Add up migration script here
+ALTER TABLE rsvp.reservation_changes
+    ADD COLUMN actor_id VARCHAR(64),
+    ADD COLUMN changed_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE INDEX reservation_changes_actor_idx ON rsvp.reservation_changes (actor_id, changed_at);
+
+-- the acting user is passed per transaction via `SET LOCAL rsvp.actor_id`
+CREATE OR REPLACE FUNCTION rsvp.reservations_trigger() RETURNS TRIGGER AS $$
+DECLARE
+    actor VARCHAR(64) := NULLIF(current_setting('rsvp.actor_id', true), '');
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO rsvp.reservation_changes (reservation_id, op, actor_id) VALUES (NEW.id, 'create', actor);
+    ELSIF TG_OP = 'UPDATE' THEN
+        IF OLD.status <> NEW.status THEN
+            INSERT INTO rsvp.reservation_changes (reservation_id, op, actor_id) VALUES (NEW.id, 'update', actor);
+        END IF;
+    ELSIF TG_OP = 'DELETE' THEN
+        INSERT INTO rsvp.reservation_changes (reservation_id, op, actor_id) VALUES (OLD.id, 'delete', actor);
+    END IF;
+    NOTIFY reservation_update;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE rsvp.reservations ADD COLUMN bundle_id uuid;
+
+CREATE INDEX reservations_bundle_id_idx ON rsvp.reservations (bundle_id) WHERE bundle_id IS NOT NULL;
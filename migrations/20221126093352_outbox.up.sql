@@ -0,0 +1,13 @@
+-- Add up migration script here
+-- events written in the same transaction as the change, polled by a relay
+CREATE TABLE rsvp.outbox (
+    id BIGSERIAL NOT NULL,
+    reservation_id uuid NOT NULL,
+    kind VARCHAR(16) NOT NULL,
+    -- the reservation after the change, see `Reservation::encode_to_vec`
+    payload BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+
+    CONSTRAINT outbox_pkey PRIMARY KEY (id),
+    CONSTRAINT outbox_kind CHECK (kind IN ('reserved', 'confirmed', 'cancelled'))
+);
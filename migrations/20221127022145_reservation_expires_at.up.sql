@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- pending holds lapse at this time, see `expire_holds`
+ALTER TABLE rsvp.reservations ADD COLUMN expires_at TIMESTAMPTZ;
+
+CREATE INDEX reservations_expires_at_idx ON rsvp.reservations (expires_at) WHERE expires_at IS NOT NULL;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE rsvp.reservations DROP COLUMN amount_cents, DROP COLUMN currency;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE rsvp.reservations
+    ADD COLUMN amount_cents BIGINT,
+    ADD COLUMN currency CHAR(3);
@@ -0,0 +1,16 @@
+-- Add up migration script here
+-- hourly rates per resource, hours are UTC. weekday follows extract(dow): 0 is Sunday, NULL is every day
+CREATE TABLE rsvp.pricing_rules (
+    id BIGSERIAL NOT NULL,
+    resource_id VARCHAR(64) NOT NULL,
+    weekday SMALLINT,
+    start_hour SMALLINT NOT NULL,
+    end_hour SMALLINT NOT NULL,
+    rate_cents BIGINT NOT NULL,
+
+    CONSTRAINT pricing_rules_pkey PRIMARY KEY (id),
+    CONSTRAINT pricing_rules_weekday CHECK (weekday BETWEEN 0 AND 6),
+    CONSTRAINT pricing_rules_hours CHECK (0 <= start_hour AND start_hour < end_hour AND end_hour <= 24),
+    CONSTRAINT pricing_rules_rate CHECK (rate_cents >= 0)
+);
+CREATE INDEX pricing_rules_resource_id_idx ON rsvp.pricing_rules (resource_id);
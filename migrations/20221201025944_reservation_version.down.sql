@@ -0,0 +1,9 @@
+-- Add down migration script here
+CREATE OR REPLACE FUNCTION rsvp.reservations_touch() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at := clock_timestamp();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE rsvp.reservations DROP COLUMN version;
@@ -0,0 +1,11 @@
+-- Add up migration script here
+ALTER TABLE rsvp.reservations ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
+
+-- every write bumps the version, see `update_if_version`
+CREATE OR REPLACE FUNCTION rsvp.reservations_touch() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at := clock_timestamp();
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+CREATE TABLE rsvp.resource_groups (
+    group_id VARCHAR(64) NOT NULL,
+    resource_id VARCHAR(64) NOT NULL,
+
+    CONSTRAINT resource_groups_pkey PRIMARY KEY (group_id, resource_id)
+);
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX rsvp.reservations_note_tsv_idx;
+ALTER TABLE rsvp.reservations DROP COLUMN note_tsv;
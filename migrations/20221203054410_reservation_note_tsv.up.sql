@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE rsvp.reservations
+    ADD COLUMN note_tsv tsvector GENERATED ALWAYS AS (to_tsvector('simple', note)) STORED;
+CREATE INDEX reservations_note_tsv_idx ON rsvp.reservations USING gin (note_tsv);
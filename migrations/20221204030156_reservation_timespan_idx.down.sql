@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX rsvp.reservations_timespan_idx;
+DROP INDEX rsvp.reservations_resource_id_timespan_idx;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- point lookups (`timespan @> t`) with or without a resource, including shared and cancelled rows
+CREATE INDEX reservations_resource_id_timespan_idx ON rsvp.reservations USING gist (resource_id, timespan);
+CREATE INDEX reservations_timespan_idx ON rsvp.reservations USING gist (timespan);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE rsvp.reservations DROP CONSTRAINT reservations_approval_status;
+ALTER TABLE rsvp.reservations DROP COLUMN approval_status;
+ALTER TABLE rsvp.reservations DROP COLUMN approver_id;
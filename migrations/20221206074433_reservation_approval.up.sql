@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE rsvp.reservations ADD COLUMN approver_id VARCHAR(64);
+ALTER TABLE rsvp.reservations ADD COLUMN approval_status VARCHAR(16) NOT NULL DEFAULT 'not_required';
+ALTER TABLE rsvp.reservations ADD CONSTRAINT reservations_approval_status
+    CHECK (approval_status IN ('not_required', 'pending', 'approved'));
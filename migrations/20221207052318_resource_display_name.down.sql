@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE rsvp.resources ALTER COLUMN capacity DROP DEFAULT;
+ALTER TABLE rsvp.resources DROP COLUMN display_name;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+ALTER TABLE rsvp.resources ADD COLUMN display_name VARCHAR(128);
+-- so a resource can be named without choosing a capacity
+ALTER TABLE rsvp.resources ALTER COLUMN capacity SET DEFAULT 1;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE rsvp.reservations DROP CONSTRAINT reservations_units;
+ALTER TABLE rsvp.reservations DROP COLUMN units;
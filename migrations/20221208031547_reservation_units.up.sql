@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- units booked of the resource's capacity, NULL counts as 1
+ALTER TABLE rsvp.reservations ADD COLUMN units INTEGER;
+ALTER TABLE rsvp.reservations ADD CONSTRAINT reservations_units CHECK (units > 0);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+ALTER TABLE rsvp.resources DROP CONSTRAINT resources_location;
+ALTER TABLE rsvp.resources DROP COLUMN lon;
+ALTER TABLE rsvp.resources DROP COLUMN lat;
@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE rsvp.resources ADD COLUMN lat DOUBLE PRECISION;
+ALTER TABLE rsvp.resources ADD COLUMN lon DOUBLE PRECISION;
+ALTER TABLE rsvp.resources ADD CONSTRAINT resources_location CHECK (
+    (lat IS NULL) = (lon IS NULL) AND lat BETWEEN -90 AND 90 AND lon BETWEEN -180 AND 180
+);
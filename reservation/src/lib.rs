@@ -1,20 +1,392 @@
+mod clock;
 mod manager;
+mod policy;
+mod sink;
 use async_trait::async_trait;
+use futures::Stream;
+use std::{collections::HashMap, pin::Pin, str::FromStr, sync::Arc};
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc,
+    Weekday,
+};
+use chrono_tz::Tz;
 use sqlx::PgPool;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use policy::{LeadTimePolicy, MaxDurationPolicy, ReservationPolicy};
+pub use sink::{NoopSink, ReservationSink};
 
 pub type ReservationId = String;
 pub type UserId = String;
 pub type ResourceId = String;
 
+/// generate a reservation id up front, e.g. to reference it in a payment flow before reserving.
+///
+/// The id only becomes a reservation once `reserve_with_id` succeeds: if that fails (conflict,
+/// validation), nothing is stored under it, so anything already referencing the id must be
+/// prepared for it to never exist.
+pub fn new_reservation_id() -> ReservationId {
+    uuid::Uuid::new_v4().to_string()
+}
+
 #[derive(Debug)]
 pub struct ReservationManager {
     pool: PgPool,
+    forbid_user_double_booking: bool,
+    auto_confirm: bool,
+    dedupe_exact_retries: bool,
+    strict_constraints: bool,
+    max_active_per_user: Option<u32>,
+    /// (max booked time, period length)
+    hours_quota: Option<(Duration, Duration)>,
+    clock: Arc<dyn Clock>,
+    lead_time: LeadTimePolicy,
+    policies: Vec<Arc<dyn ReservationPolicy>>,
+    events: broadcast::Sender<ReservationEvent>,
+    sink: Arc<dyn ReservationSink>,
+    /// schemas holding per-tenant `reservations` tables, see `cross_tenant_overlaps`
+    tenant_schemas: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageDirection {
+    /// rows ordered before the cursor (previous page)
+    Before,
+    /// rows ordered after the cursor (next page)
+    After,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CursorPage {
+    pub rsvps: Vec<abi::Reservation>,
+    /// cursor to pass with `PageDirection::After` for the next page, `None` on the last page
+    pub next: Option<ReservationId>,
+    /// cursor to pass with `PageDirection::Before` for the previous page, `None` on the first page
+    pub prev: Option<ReservationId>,
+}
+
+/// a nullable reservation column that may need backfilling on legacy rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequiredField {
+    CreatedBy,
+    AmountCents,
+    Currency,
+}
+
+impl RequiredField {
+    fn column(&self) -> &'static str {
+        match self {
+            Self::CreatedBy => "created_by",
+            Self::AmountCents => "amount_cents",
+            Self::Currency => "currency",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutboxEventKind {
+    Reserved,
+    Confirmed,
+    Cancelled,
+}
+
+impl OutboxEventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reserved => "reserved",
+            Self::Confirmed => "confirmed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl FromStr for OutboxEventKind {
+    type Err = abi::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reserved" => Ok(Self::Reserved),
+            "confirmed" => Ok(Self::Confirmed),
+            "cancelled" => Ok(Self::Cancelled),
+            _ => Err(abi::Error::Unknown),
+        }
+    }
+}
+
+/// a change recorded in the same transaction as the change itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEvent {
+    pub id: i64,
+    pub kind: OutboxEventKind,
+    pub reservation: abi::Reservation,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservationEventKind {
+    Held,
+    HoldExpired,
+}
+
+/// a live notification of a change. Events sent while nobody listens are dropped,
+/// use the outbox where every change must be seen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReservationEvent {
+    pub kind: ReservationEventKind,
+    pub reservation: abi::Reservation,
+}
+
+pub type ReservationEventStream = Pin<Box<dyn Stream<Item = ReservationEvent> + Send>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+}
+
+/// a slot repeating at the same local wall-clock time in `tz`, following its DST shifts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecurrenceRule {
+    /// local start of the first occurrence
+    pub start: NaiveDateTime,
+    pub duration: Duration,
+    pub frequency: Frequency,
+    pub tz: Tz,
+}
+
+impl RecurrenceRule {
+    /// the first `count` occurrence windows. A local start time that is skipped by a DST
+    /// transition moves to the first valid time after it; an ambiguous one takes the earlier.
+    pub fn occurrences(&self, count: u32) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+        let step = match self.frequency {
+            Frequency::Daily => Duration::days(1),
+            Frequency::Weekly => Duration::weeks(1),
+        };
+
+        (0..count)
+            .filter_map(|i| {
+                let local = self.start + step * i as i32;
+                let start = self.tz.from_local_datetime(&local).earliest().or_else(|| {
+                    // in a spring-forward gap, which is at most an hour long
+                    self.tz
+                        .from_local_datetime(&(local + Duration::hours(1)))
+                        .earliest()
+                })?;
+                let start = start.with_timezone(&Utc);
+                Some((start, start + self.duration))
+            })
+            .collect()
+    }
+}
+
+/// a window relative to the current time, in whole days
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativeWindow {
+    /// `[now - n days, now)`
+    LastDays(u32),
+    /// `[now, now + n days)`
+    NextDays(u32),
+}
+
+impl RelativeWindow {
+    pub fn resolve(&self, now: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        match *self {
+            Self::LastDays(n) => (now - Duration::days(n.into()), now),
+            Self::NextDays(n) => (now, now + Duration::days(n.into())),
+        }
+    }
+}
+
+/// a reservation decorated for front-end calendars
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarEvent {
+    pub id: ReservationId,
+    pub title: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub color: &'static str,
+}
+
+impl CalendarEvent {
+    pub const PENDING_COLOR: &'static str = "#ffbf00";
+    pub const CONFIRMED_COLOR: &'static str = "#4caf50";
+    pub const BLOCKED_COLOR: &'static str = "#f44336";
+    pub const OTHER_COLOR: &'static str = "#9e9e9e";
+}
+
+impl TryFrom<abi::Reservation> for CalendarEvent {
+    type Error = abi::Error;
+
+    fn try_from(rsvp: abi::Reservation) -> Result<Self, Self::Error> {
+        let color = match abi::ReservationStatus::from_i32(rsvp.status) {
+            Some(abi::ReservationStatus::Pending) => Self::PENDING_COLOR,
+            Some(abi::ReservationStatus::Confirmed) => Self::CONFIRMED_COLOR,
+            Some(abi::ReservationStatus::Blocked) => Self::BLOCKED_COLOR,
+            _ => Self::OTHER_COLOR,
+        };
+
+        Ok(Self {
+            title: format!("{} @ {}", rsvp.user_id, rsvp.resource_id),
+            start: abi::to_datetime(rsvp.start_time.as_ref())?,
+            end: abi::to_datetime(rsvp.end_time.as_ref())?,
+            id: rsvp.id,
+            color,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    /// rows added to `rsvp.reservations`
+    pub imported: u64,
+    /// active pairs the conflict constraint would reject: an imported row first, then a later
+    /// imported row or a stored one
+    pub overlaps: Vec<(abi::Reservation, abi::Reservation)>,
+    /// imported shared rows whose resource is booked beyond its capacity during them
+    pub over_capacity: Vec<abi::Reservation>,
+    /// whether the conflict constraint was dropped to take the overlapping rows. It stays dropped
+    /// until they are resolved, see `ReservationManager::restore_conflict_constraint`.
+    pub constraint_dropped: bool,
+}
+
+/// how much of a resource's capacity is booked during `[start, end)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotAvailability {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub used: i32,
+    pub capacity: i32,
+}
+
+impl SlotAvailability {
+    pub fn remaining(&self) -> i32 {
+        (self.capacity - self.used).max(0)
+    }
+}
+
+/// catalog entry of a resource, see `set_display_name`, `set_capacity` and `set_location`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResourceInfo {
+    pub id: ResourceId,
+    pub display_name: Option<String>,
+    pub capacity: i32,
+    /// (latitude, longitude) in degrees
+    pub location: Option<(f64, f64)>,
+}
+
+impl ResourceInfo {
+    /// what a resource without a catalog entry behaves as
+    pub fn unregistered(id: impl Into<ResourceId>) -> Self {
+        Self {
+            id: id.into(),
+            display_name: None,
+            capacity: 1,
+            location: None,
+        }
+    }
+}
+
+/// what a confirmation email or printout shows for a reservation, see `receipt`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Receipt {
+    pub id: ReservationId,
+    pub reference: i64,
+    /// the resource's display name, or its id if it has none
+    pub resource_name: String,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    pub duration: Duration,
+    pub status: abi::ReservationStatus,
+    pub amount_cents: Option<i64>,
+    pub currency: Option<String>,
+}
+
+/// a reservation as seen by one user, see `query_for_viewer`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewerReservation {
+    pub reservation: abi::Reservation,
+    pub is_mine: bool,
+}
+
+impl ViewerReservation {
+    fn new(mut reservation: abi::Reservation, viewer_id: &str) -> Self {
+        let is_mine = reservation.user_id == viewer_id;
+        if !is_mine {
+            // others only see the slot is busy
+            reservation.user_id.clear();
+            reservation.created_by.clear();
+            reservation.approver_id.clear();
+            reservation.note.clear();
+            reservation.special_requests.clear();
+        }
+        Self {
+            reservation,
+            is_mine,
+        }
+    }
+}
+
+/// an hourly rate applying to `[start_hour, end_hour)` UTC, on `weekday` or on every day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PricingRule {
+    pub weekday: Option<Weekday>,
+    pub start_hour: u32,
+    pub end_hour: u32,
+    pub rate_cents: i64,
+}
+
+impl PricingRule {
+    fn applies_to(&self, at: DateTime<Utc>) -> bool {
+        self.weekday.is_none_or(|d| d == at.weekday())
+            && self.start_hour <= at.hour()
+            && at.hour() < self.end_hour
+    }
+}
+
+/// fields to change in `update_if_version`, `None` leaves a field as is
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReservationChanges {
+    pub note: Option<String>,
+    pub window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+/// what a client holding some (id, version) pairs must change to match a query
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncDiff {
+    /// matching rows the client doesn't know
+    pub added: Vec<abi::Reservation>,
+    /// known rows whose version advanced
+    pub updated: Vec<abi::Reservation>,
+    /// known ids no longer matching, or deleted
+    pub removed: Vec<ReservationId>,
+}
+
+// created is the common outcome, boxing it would only add an allocation
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReserveOutcome {
+    Created(abi::Reservation),
+    Conflict(abi::ReservationConflictInfo),
+    Invalid(abi::ValidationError),
 }
 
 #[async_trait]
 pub trait Rsvp {
     async fn reserve(&self, rsvp: abi::Reservation) -> Result<abi::Reservation, abi::Error>;
-    async fn change_status(&self, id: ReservationId) -> Result<abi::Reservation, abi::Error>;
+    async fn reserve_batch_best_effort(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+    ) -> Result<Vec<Result<abi::Reservation, abi::Error>>, abi::Error>;
+    /// confirm a pending reservation, recording `actor_id` (empty if unknown) in the change log.
+    /// Confirming a hold clears its expiry, holds past their expiry can't be confirmed.
+    async fn change_status(
+        &self,
+        id: ReservationId,
+        actor_id: &str,
+    ) -> Result<abi::Reservation, abi::Error>;
     async fn update_note(
         &self,
         id: ReservationId,
@@ -26,4 +398,390 @@ pub trait Rsvp {
         &self,
         query: abi::ReservationQuery,
     ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// like `query`, but rows are sent as they are read. Reading stops once the receiver is
+    /// dropped, and a slow receiver holds the producer back.
+    async fn query_stream(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> Result<mpsc::Receiver<Result<abi::Reservation, abi::Error>>, abi::Error>;
+    async fn neighbors(
+        &self,
+        id: ReservationId,
+    ) -> Result<(Option<abi::Reservation>, Option<abi::Reservation>), abi::Error>;
+    async fn occupancy_snapshot(
+        &self,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Vec<(ResourceId, abi::Reservation)>, abi::Error>;
+    async fn cancel(&self, id: ReservationId) -> Result<abi::Reservation, abi::Error>;
+    /// page through reservations ordered by start time, relative to the reservation `cursor`.
+    /// Without a cursor, `After` returns the first page and `Before` the last one.
+    async fn query_by_cursor(
+        &self,
+        query: abi::ReservationQuery,
+        cursor: Option<ReservationId>,
+        direction: PageDirection,
+    ) -> Result<CursorPage, abi::Error>;
+    /// like `reserve`, but report invalid input and conflicts as outcomes.
+    /// Only infrastructure failures and other rejections are returned as errors.
+    async fn try_reserve(&self, rsvp: abi::Reservation) -> Result<ReserveOutcome, abi::Error>;
+    /// earliest start at or after `from` with a free window of `duration`, for each resource
+    async fn next_available_per_resource(
+        &self,
+        resources: Vec<ResourceId>,
+        from: DateTime<Utc>,
+        duration: Duration,
+    ) -> Result<HashMap<ResourceId, Option<DateTime<Utc>>>, abi::Error>;
+    async fn add_blackout(
+        &self,
+        resource_id: ResourceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<(), abi::Error>;
+    /// a hash of the ids and last modification times of the rows `query` would return
+    async fn query_etag(&self, query: abi::ReservationQuery) -> Result<String, abi::Error>;
+    /// atomically exchange the time slots of two reservations, each keeping its resource
+    async fn swap(
+        &self,
+        id_a: ReservationId,
+        id_b: ReservationId,
+    ) -> Result<(abi::Reservation, abi::Reservation), abi::Error>;
+    /// reserve under a caller-chosen id, see `new_reservation_id`
+    async fn reserve_with_id(
+        &self,
+        id: ReservationId,
+        rsvp: abi::Reservation,
+    ) -> Result<abi::Reservation, abi::Error>;
+    /// count reservations matching the query's filters by duration class
+    async fn duration_class_counts(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> Result<HashMap<abi::DurationClass, i64>, abi::Error>;
+    /// confirmed reservations ending in `[now, now + within)`
+    async fn ending_soon(
+        &self,
+        within: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// still pending reservations starting in `[now, now + within]`
+    async fn pending_starting_soon(
+        &self,
+        within: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// look up a reservation by its sequential booking reference
+    async fn get_by_reference(&self, reference: i64) -> Result<abi::Reservation, abi::Error>;
+    /// allow overlapping reservations on the resource up to `capacity` units in total,
+    /// each reservation taking its `units` (1 if unset). Resources default to 1.
+    /// Fails with `CapacityExceeded` while the existing reservations need more than `capacity`.
+    async fn set_capacity(&self, resource_id: ResourceId, capacity: i32) -> Result<(), abi::Error>;
+    /// split `[start, end)` at every booking edge and report the used capacity of each segment.
+    /// This and the other heavy reads taking a `cancel` token stop their query and fail with
+    /// `Error::Cancelled` once it is cancelled.
+    async fn availability_for(
+        &self,
+        resource_id: ResourceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        cancel: Option<CancellationToken>,
+    ) -> Result<Vec<SlotAvailability>, abi::Error>;
+    async fn add_pricing_rule(
+        &self,
+        resource_id: ResourceId,
+        rule: PricingRule,
+    ) -> Result<(), abi::Error>;
+    /// price of booking the resource for `[start, end)`, each hour charged at its rule's rate
+    /// and partial hours pro rata. A weekday rule wins over an every-day rule for the same hour,
+    /// hours without a rule are free.
+    async fn quote(
+        &self,
+        resource_id: ResourceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<i64, abi::Error>;
+    /// reservations whose status was changed by `actor_id`, optionally only changes since `since`
+    async fn modified_by(
+        &self,
+        actor_id: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// book the same window on every resource in one transaction, all or nothing.
+    /// The reservations share a new `bundle_id`.
+    async fn reserve_bundle(
+        &self,
+        user_id: UserId,
+        resource_ids: Vec<ResourceId>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        note: String,
+    ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    async fn get_bundle(&self, bundle_id: String) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// like `query`, but within `spec` relative to `now` (the manager's clock if `None`)
+    /// instead of the query's own start and end
+    async fn relative(
+        &self,
+        spec: RelativeWindow,
+        now: Option<DateTime<Utc>>,
+        filter: abi::ReservationQuery,
+    ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// the first reservation in the query's order, ignoring its paging
+    async fn first(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> Result<Option<abi::Reservation>, abi::Error>;
+    /// the last reservation in the query's order, ignoring its paging
+    async fn last(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> Result<Option<abi::Reservation>, abi::Error>;
+    /// move the reservations of resource `from` (only those within `window`, if given) to `to`.
+    /// Fails without moving anything if a moved reservation would conflict on `to`,
+    /// and with `InvalidResourceId` if `to` is `from`.
+    async fn reassign_resource(
+        &self,
+        from: &str,
+        to: &str,
+        window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    ) -> Result<u64, abi::Error>;
+    /// `query` results as calendar events, colored by status
+    async fn calendar_events(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> Result<Vec<CalendarEvent>, abi::Error>;
+    /// each occurrence of `rule` on the resource, with whether it is still free and not blacked out
+    async fn recurring_availability(
+        &self,
+        resource_id: ResourceId,
+        rule: RecurrenceRule,
+        count: u32,
+    ) -> Result<Vec<((DateTime<Utc>, DateTime<Utc>), bool)>, abi::Error>;
+    /// remove and return up to `limit` of the oldest outbox events, for a relay to publish.
+    /// Concurrent relays receive disjoint events.
+    async fn drain_outbox(&self, limit: i64) -> Result<Vec<OutboxEvent>, abi::Error>;
+    /// share of the resource's capacity taken by confirmed reservations over `[start, end)`
+    async fn utilization(
+        &self,
+        resource_id: ResourceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        cancel: Option<CancellationToken>,
+    ) -> Result<f64, abi::Error>;
+    /// reserve as a pending hold that lapses after `ttl` unless confirmed
+    async fn hold(
+        &self,
+        rsvp: abi::Reservation,
+        ttl: Duration,
+    ) -> Result<abi::Reservation, abi::Error>;
+    /// cancel pending holds whose expiry has passed, returning them
+    async fn expire_holds(&self) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// pairs of the user's active reservations overlapping each other, on any resources
+    async fn user_schedule_conflicts(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<(abi::Reservation, abi::Reservation)>, abi::Error>;
+    /// number of distinct users with active reservations on the resource, within `window` if given
+    async fn distinct_user_count(
+        &self,
+        resource_id: ResourceId,
+        window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+        include_blocked: bool,
+    ) -> Result<i64, abi::Error>;
+    /// sum of the amounts of the confirmed reservations matching the query's filters, whatever
+    /// its status. Amounts in different currencies are added as is.
+    async fn revenue(&self, query: abi::ReservationQuery) -> Result<i64, abi::Error>;
+    /// reservations where `field` is NULL, oldest first
+    async fn find_incomplete(
+        &self,
+        field: RequiredField,
+    ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// the parts of `[start, end)` not covered by any of the user's active reservations
+    async fn user_free_gaps(
+        &self,
+        user_id: UserId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        cancel: Option<CancellationToken>,
+    ) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>, abi::Error>;
+    /// like `reserve`, but report every validation problem at once as `Error::InvalidReservation`
+    async fn reserve_validated(
+        &self,
+        rsvp: abi::Reservation,
+    ) -> Result<abi::Reservation, abi::Error>;
+    /// move the resource's confirmed bookings starting at or after `from` as early as possible,
+    /// keeping their order and durations. Other bookings and blackouts stay where they are.
+    async fn compact_schedule(
+        &self,
+        resource_id: ResourceId,
+        from: DateTime<Utc>,
+    ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// apply `changes` only if the reservation is still at `expected_version`,
+    /// otherwise fail with `VersionConflict` carrying the stored version.
+    /// A new window goes through the same policy, blackout and capacity checks as `reserve`.
+    async fn update_if_version(
+        &self,
+        id: ReservationId,
+        expected_version: i64,
+        changes: ReservationChanges,
+    ) -> Result<abi::Reservation, abi::Error>;
+    async fn add_to_group(&self, group: String, resource_id: ResourceId) -> Result<(), abi::Error>;
+    /// book the first resource of `group` (by resource id) that is free for the window.
+    /// The assigned resource is the returned reservation's `resource_id`.
+    async fn reserve_from_group(
+        &self,
+        user_id: UserId,
+        group: String,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        note: String,
+    ) -> Result<abi::Reservation, abi::Error>;
+    /// count non-cancelled reservations per (weekday, hour) cell of local time in `tz`.
+    /// Bookings are clamped to `[start, end)` and count once for every hour they touch.
+    /// Only non-empty cells are returned, ordered from Monday 00:00.
+    async fn heatmap(
+        &self,
+        resource_id: ResourceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        tz: FixedOffset,
+        cancel: Option<CancellationToken>,
+    ) -> Result<Vec<((Weekday, u8), i64)>, abi::Error>;
+    /// live `Held` / `HoldExpired` events of the resource. A subscriber falling too far
+    /// behind skips the events it missed.
+    fn subscribe_resource(&self, resource_id: ResourceId) -> ReservationEventStream;
+    /// reservations matching `filter` whose note contains `term`, best full-text match first.
+    /// Terms too short to rank are matched as a substring and ordered by start time.
+    async fn search_ranked(
+        &self,
+        term: String,
+        filter: abi::ReservationQuery,
+    ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// non-cancelled reservations whose `[start, end)` contains `t`, optionally on one resource
+    async fn at_instant(
+        &self,
+        resource_id: Option<&str>,
+        t: DateTime<Utc>,
+    ) -> Result<Vec<abi::Reservation>, abi::Error>;
+    /// like `reserve`, but if the slot is taken, retry with the window moved later in
+    /// steps of 5 minutes, up to `auto_shift`. Returns the booking and the shift applied.
+    async fn reserve_with_shift(
+        &self,
+        rsvp: abi::Reservation,
+        auto_shift: Option<Duration>,
+    ) -> Result<(abi::Reservation, Duration), abi::Error>;
+    /// the user's non-cancelled reservations overlapping `[start, end)`, merged into sessions:
+    /// bookings overlapping or less than 5 minutes apart belong to the same session
+    async fn user_sessions(
+        &self,
+        user_id: UserId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>, abi::Error>;
+    /// compare the client's known (id, version) pairs against the rows matching `filter`
+    async fn sync_diff(
+        &self,
+        known: Vec<(ReservationId, i64)>,
+        filter: abi::ReservationQuery,
+    ) -> Result<SyncDiff, abi::Error>;
+    /// the longest non-cancelled reservation of every resource, optionally only among those
+    /// within `window`. Ties go to the earlier one.
+    async fn longest_per_resource(
+        &self,
+        window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    ) -> Result<HashMap<ResourceId, abi::Reservation>, abi::Error>;
+    /// confirm a reservation awaiting approval. Only its assigned approver may do so,
+    /// anyone else gets `NotAuthorized`.
+    async fn approve(
+        &self,
+        id: ReservationId,
+        approver_id: &str,
+    ) -> Result<abi::Reservation, abi::Error>;
+    /// non-cancelled reservations starting in each month of `year`, local time in `tz`.
+    /// Index 0 is January. A booking spanning a month boundary counts in its start month only.
+    async fn monthly_counts(
+        &self,
+        resource_id: Option<&str>,
+        year: i32,
+        tz: FixedOffset,
+    ) -> Result<[i64; 12], abi::Error>;
+    /// book `rsvp` plus blocked reservations of `buffer` length right before and after it,
+    /// all or nothing. Returns the main reservation and the buffers in time order.
+    async fn reserve_exclusive(
+        &self,
+        rsvp: abi::Reservation,
+        buffer: Duration,
+    ) -> Result<(abi::Reservation, Vec<abi::Reservation>), abi::Error>;
+    /// name the resource for display, registering it with capacity 1 if it's not in the catalog
+    async fn set_display_name(
+        &self,
+        resource_id: ResourceId,
+        display_name: String,
+    ) -> Result<(), abi::Error>;
+    /// like `query`, with each reservation's resource catalog entry.
+    /// Resources without an entry get `ResourceInfo::unregistered`.
+    async fn query_with_resource(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> Result<Vec<(abi::Reservation, ResourceInfo)>, abi::Error>;
+    /// free gaps shorter than `shorter_than` between consecutive confirmed bookings of the
+    /// resource within `window`. Time before the first and after the last booking isn't a gap.
+    async fn short_gaps(
+        &self,
+        resource_id: ResourceId,
+        window: (DateTime<Utc>, DateTime<Utc>),
+        shorter_than: Duration,
+    ) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>, abi::Error>;
+    /// a printable summary of the reservation with its times in `tz`
+    async fn receipt(&self, id: ReservationId, tz: FixedOffset) -> Result<Receipt, abi::Error>;
+    /// pairs of active reservations in different tenant schemas (see `with_tenant_schemas`)
+    /// sharing a resource id with overlapping windows, a sign of a misconfigured shared resource.
+    /// Empty unless at least two schemas are configured.
+    async fn cross_tenant_overlaps(
+        &self,
+    ) -> Result<Vec<(abi::Reservation, abi::Reservation)>, abi::Error>;
+    /// like `query`, with reservations of users other than `viewer_id` redacted
+    /// (user, creator and approver ids, note and special requests cleared).
+    async fn query_for_viewer(
+        &self,
+        query: abi::ReservationQuery,
+        viewer_id: UserId,
+    ) -> Result<Vec<ViewerReservation>, abi::Error>;
+    /// place the resource at (`lat`, `lon`) degrees, registering it with capacity 1 if needed
+    async fn set_location(
+        &self,
+        resource_id: ResourceId,
+        lat: f64,
+        lon: f64,
+    ) -> Result<(), abi::Error>;
+    /// located resources within `radius_km` of `center` (lat, lon) having a free slot of
+    /// `duration` inside `window`, with the earliest such start, nearest first.
+    /// Any active booking blocks its slot, regardless of capacity, and so do blackouts.
+    async fn nearest_available(
+        &self,
+        center: (f64, f64),
+        radius_km: f64,
+        window: (DateTime<Utc>, DateTime<Utc>),
+        duration: Duration,
+    ) -> Result<Vec<(ResourceInfo, DateTime<Utc>)>, abi::Error>;
+    /// cancelled / (confirmed + cancelled) among reservations overlapping `window`, on the
+    /// resource if given, whenever they were cancelled. 0.0 when there are none.
+    async fn cancellation_rate(
+        &self,
+        resource_id: Option<&str>,
+        window: (DateTime<Utc>, DateTime<Utc>),
+    ) -> Result<f64, abi::Error>;
+    /// split the reservation at `at` into `[start, at)`, kept under its id, and a new adjacent
+    /// `[at, end)` with the same user, resource, status and note, in one transaction.
+    async fn split(
+        &self,
+        id: ReservationId,
+        at: DateTime<Utc>,
+    ) -> Result<(abi::Reservation, abi::Reservation), abi::Error>;
+    /// busy flags of the `slot_minutes` long slots of local `date` in `tz`, a slot being busy
+    /// if any confirmed reservation on the resource overlaps it
+    async fn slot_bitmap(
+        &self,
+        resource_id: ResourceId,
+        date: NaiveDate,
+        slot_minutes: u32,
+        tz: FixedOffset,
+    ) -> Result<Vec<bool>, abi::Error>;
 }
@@ -1,58 +1,125 @@
-use crate::{ReservationId, ReservationManager, Rsvp};
-use abi::{ReservationStatus, Validator};
+use crate::{
+    CalendarEvent, Clock, CursorPage, ImportReport, LeadTimePolicy, NoopSink, OutboxEvent,
+    OutboxEventKind, PageDirection, PricingRule, Receipt, RecurrenceRule, RelativeWindow,
+    RequiredField, ReservationChanges, ReservationEvent, ReservationEventKind,
+    ReservationEventStream, ReservationId, ReservationManager, ReservationPolicy, ReservationSink,
+    ReserveOutcome, ResourceId, ResourceInfo, Rsvp, SlotAvailability, SyncDiff, SystemClock,
+    UserId, ViewerReservation,
+};
+use abi::{
+    ApprovalStatus, DurationClass, ReservationConflictInfo, ReservationStatus, ReservationWindow,
+    Validator,
+};
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
-use sqlx::{postgres::types::PgRange, types::Uuid, PgPool, Row};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Timelike, Utc, Weekday,
+};
+use futures::{Stream, StreamExt};
+use sqlx::{
+    postgres::{types::PgRange, PgArguments},
+    query::QueryAs,
+    types::Uuid,
+    PgConnection, PgPool, Postgres, Row, Transaction,
+};
+use std::{collections::HashMap, future::Future, sync::Arc};
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
 
 #[async_trait]
 impl Rsvp for ReservationManager {
     async fn reserve(&self, mut rsvp: abi::Reservation) -> Result<abi::Reservation, abi::Error> {
-        rsvp.validate()?;
+        rsvp.id.clear();
+        rsvp.bundle_id.clear();
 
-        let status = ReservationStatus::from_i32(rsvp.status).unwrap_or(ReservationStatus::Pending);
+        let mut tx = self.pool.begin().await?;
+        let rsvp = self.insert(&mut tx, rsvp).await?;
+        self.commit_reserved(tx, std::slice::from_ref(&rsvp))
+            .await?;
 
-        let range: PgRange<DateTime<Utc>> = rsvp.get_timespan();
+        Ok(rsvp)
+    }
 
-        let sql = r#"
-            INSERT INTO rsvp.reservations (user_id, resource_id, timespan, note, status) 
-            VALUES ($1, $2, $3, $4, $5::rsvp.reservation_status) RETURNING id
-        "#;
-        let id: Uuid = sqlx::query(sql)
-            .bind(rsvp.user_id.clone())
-            .bind(rsvp.resource_id.clone())
-            .bind(range)
-            .bind(rsvp.note.clone())
-            .bind(status.to_string())
-            .fetch_one(&self.pool)
-            .await?
-            .get("id");
+    async fn reserve_batch_best_effort(
+        &self,
+        rsvps: Vec<abi::Reservation>,
+    ) -> Result<Vec<Result<abi::Reservation, abi::Error>>, abi::Error> {
+        let mut results = Vec::with_capacity(rsvps.len());
+        for rsvp in rsvps {
+            let mut tx = self.pool.begin().await?;
+            match self
+                .insert(
+                    &mut tx,
+                    abi::Reservation {
+                        id: "".into(),
+                        ..rsvp
+                    },
+                )
+                .await
+            {
+                Ok(rsvp) => {
+                    self.commit_reserved(tx, std::slice::from_ref(&rsvp))
+                        .await?;
+                    results.push(Ok(rsvp));
+                }
+                Err(e) => {
+                    tx.rollback().await?;
+                    results.push(Err(e));
+                }
+            }
+        }
 
-        rsvp.id = id.to_string();
-        Ok(rsvp)
+        Ok(results)
     }
 
-    async fn change_status(&self, id: ReservationId) -> Result<abi::Reservation, abi::Error> {
+    async fn change_status(
+        &self,
+        id: ReservationId,
+        actor_id: &str,
+    ) -> Result<abi::Reservation, abi::Error> {
         let id = Uuid::parse_str(&id).map_err(|_| abi::Error::InvalidReservationId(id.clone()))?;
-        let rsvp = sqlx::query_as::<_, abi::Reservation>(r#"
-        UPDATE rsvp.reservations SET status = 'confirmed' WHERE id = $1 AND status = 'pending' RETURNING *
-        "#)
+        let mut tx = self.pool.begin().await?;
+        // picked up by the change log trigger
+        sqlx::query("SELECT set_config('rsvp.actor_id', $1, true)")
+            .bind(actor_id)
+            .execute(&mut *tx)
+            .await?;
+        // reservations awaiting approval can only be confirmed through `approve`
+        let approval: Option<String> = sqlx::query_scalar(
+            "SELECT approval_status FROM rsvp.reservations WHERE id = $1 FOR UPDATE",
+        )
         .bind(id)
-        .fetch_one(&self.pool)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if approval.as_deref() == Some("pending") {
+            return Err(abi::Error::NotAuthorized);
+        }
+        let rsvp = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+        UPDATE rsvp.reservations SET status = 'confirmed', expires_at = NULL
+        WHERE id = $1 AND status = 'pending' AND (expires_at IS NULL OR expires_at > $2) RETURNING *
+        "#,
+        )
+        .bind(id)
+        .bind(self.clock.now())
+        .fetch_one(&mut *tx)
         .await?;
+        write_outbox(&mut tx, OutboxEventKind::Confirmed, &rsvp).await?;
+        tx.commit().await?;
 
         Ok(rsvp)
     }
 
-
     async fn update_note(
         &self,
         id: ReservationId,
         note: String,
     ) -> Result<abi::Reservation, abi::Error> {
         let id = Uuid::parse_str(&id).map_err(|_| abi::Error::InvalidReservationId(id.clone()))?;
-        let rsvp = sqlx::query_as::<_, abi::Reservation>(r#"
+        let rsvp = sqlx::query_as::<_, abi::Reservation>(
+            r#"
         UPDATE rsvp.reservations SET note = $1 WHERE id = $2 RETURNING *
-        "#)
+        "#,
+        )
         .bind(note)
         .bind(id)
         .fetch_one(&self.pool)
@@ -64,23 +131,25 @@ impl Rsvp for ReservationManager {
     async fn delete(&self, id: ReservationId) -> Result<(), abi::Error> {
         let id = Uuid::parse_str(&id).map_err(|_| abi::Error::InvalidReservationId(id.clone()))?;
         let _ = sqlx::query("DELETE FROM rsvp.reservations WHERE id = $1")
-        .bind(id)
-        .execute(&self.pool)
-        // .fetch_optional(&self.pool)
-        .await?;
-        
+            .bind(id)
+            .execute(&self.pool)
+            // .fetch_optional(&self.pool)
+            .await?;
+
         Ok(())
     }
 
     async fn get(&self, id: ReservationId) -> Result<abi::Reservation, abi::Error> {
         let id = Uuid::parse_str(&id).map_err(|_| abi::Error::InvalidReservationId(id.clone()))?;
-        let rsvp = sqlx::query_as::<_, abi::Reservation>(r#"
+        let rsvp = sqlx::query_as::<_, abi::Reservation>(
+            r#"
         SELECT * FROM rsvp.reservations WHERE id = $1
-        "#)
+        "#,
+        )
         .bind(id)
         .fetch_one(&self.pool)
         .await?;
-        
+
         Ok(rsvp)
     }
 
@@ -88,248 +157,7541 @@ impl Rsvp for ReservationManager {
         &self,
         query: abi::ReservationQuery,
     ) -> Result<Vec<abi::Reservation>, abi::Error> {
-        let user_id = str_to_option(&query.user_id);
-        let resource_id = str_to_option(&query.resource_id);
-        let timespan = query.timespan();
-        let status = ReservationStatus::from_i32(query.status)
-            .unwrap_or(ReservationStatus::Pending);
+        query.validate()?;
 
-        let rsvps = sqlx::query_as::<_, abi::Reservation>("SELECT * FROM rsvp.query($1, $2, $3, $4::rsvp.reservation_status, $5, $6, $7)")
-            .bind(user_id)
-            .bind(resource_id)
-            .bind(timespan)
-            .bind(status.to_string())
-            .bind(query.page)
-            .bind(query.desc)
-            .bind(query.pagesize)
-            .fetch_all(&self.pool)
+        let sql = format!("SELECT * FROM {}", QUERY_CALL);
+        let q = bind_query(sqlx::query_as::<_, abi::Reservation>(&sql), &query);
+        let rsvps = if query.note_regex.is_some() {
+            // guard against pathological patterns
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(&format!(
+                "SET LOCAL statement_timeout = '{}'",
+                REGEX_STATEMENT_TIMEOUT
+            ))
+            .execute(&mut tx)
             .await?;
+            let rsvps = q.fetch_all(&mut tx).await?;
+            tx.commit().await?;
+            rsvps
+        } else {
+            q.fetch_all(&self.pool).await?
+        };
 
         Ok(rsvps)
     }
-}
 
-fn str_to_option(s: &str) -> Option<&str> {
-    if s.is_empty() {
-        None
-    } else {
-        Some(s)
-    }
-}
+    async fn query_stream(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> Result<mpsc::Receiver<Result<abi::Reservation, abi::Error>>, abi::Error> {
+        query.validate()?;
 
-impl ReservationManager {
-    pub fn new(pool: PgPool) -> ReservationManager {
-        Self { pool }
+        let pool = self.pool.clone();
+        let (sender, receiver) = mpsc::channel(QUERY_STREAM_BUFFER);
+        tokio::spawn(async move {
+            let mut tx = match pool.begin().await {
+                Ok(tx) => tx,
+                Err(e) => {
+                    let _ = sender.send(Err(e.into())).await;
+                    return;
+                }
+            };
+            if query.note_regex.is_some() {
+                let timeout = format!(
+                    "SET LOCAL statement_timeout = '{}'",
+                    REGEX_STATEMENT_TIMEOUT
+                );
+                if let Err(e) = sqlx::query(&timeout).execute(&mut tx).await {
+                    let _ = sender.send(Err(e.into())).await;
+                    return;
+                }
+            }
+
+            let sql = format!("SELECT * FROM {}", QUERY_CALL);
+            let mut rows =
+                bind_query(sqlx::query_as::<_, abi::Reservation>(&sql), &query).fetch(&mut tx);
+            while let Some(row) = rows.next().await {
+                // the receiver is gone, stop reading
+                if sender.send(row.map_err(abi::Error::from)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(receiver)
     }
-}
 
-#[cfg(test)]
-mod tests {
+    async fn neighbors(
+        &self,
+        id: ReservationId,
+    ) -> Result<(Option<abi::Reservation>, Option<abi::Reservation>), abi::Error> {
+        let rsvp = self.get(id).await?;
+        let timespan = rsvp.get_timespan();
 
-    use abi::{Reservation, ReservationConflictInfo, ReservationConflict, ReservationWindow, ReservationQueryBuilder};
-    use chrono::{DateTime, FixedOffset};
+        let before = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            SELECT * FROM rsvp.reservations
+            WHERE resource_id = $1 AND upper(timespan) <= lower($2::tstzrange)
+                AND status <> 'cancelled'
+            ORDER BY upper(timespan) DESC LIMIT 1
+            "#,
+        )
+        .bind(&rsvp.resource_id)
+        .bind(timespan.clone())
+        .fetch_optional(&self.pool)
+        .await?;
 
-    use super::*;
+        let after = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            SELECT * FROM rsvp.reservations
+            WHERE resource_id = $1 AND lower(timespan) >= upper($2::tstzrange)
+                AND status <> 'cancelled'
+            ORDER BY lower(timespan) ASC LIMIT 1
+            "#,
+        )
+        .bind(&rsvp.resource_id)
+        .bind(timespan)
+        .fetch_optional(&self.pool)
+        .await?;
 
-    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
-    async fn reserve_should_work_for_valid_window() {
-        let manager = ReservationManager::new(migrated_pool.clone());
-        let start: DateTime<FixedOffset> = "2022-12-25T15:00:00-0700".parse().unwrap();
-        let end: DateTime<FixedOffset> = "2022-12-28T12:00:00-0700".parse().unwrap();
-        let rsvp = Reservation::new_pending("tyrid", "1121", start, end, "......");
-        let res = manager.reserve(rsvp).await;
-        assert!(res.is_ok());
+        Ok((before, after))
     }
 
-    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
-    async fn reserve_conflict_reservation_should_reject() {
-        let manager = ReservationManager::new(migrated_pool.clone());
-        let rsvp1 = Reservation::new_pending(
-            "tyrid",
-            "1121",
-            "2022-12-25T15:00:00-0700".parse().unwrap(),
-            "2022-12-28T12:00:00-0700".parse().unwrap(),
-            "hello",
-        );
-        let rsvp2 = Reservation::new_pending(
-            "aliceid",
-            "1121",
-            "2022-12-26T15:00:00-0700".parse().unwrap(),
-            "2022-12-30T12:00:00-0700".parse().unwrap(),
-            "world",
-        );
-
-        let _rsvp1 = manager.reserve(rsvp1).await.unwrap();
-        let err = manager.reserve(rsvp2).await.unwrap_err();
-       
-        let info = ReservationConflictInfo::Parsed(ReservationConflict {
-            new: ReservationWindow { 
-                rid: "1121".to_string(), 
-                start: "2022-12-26T15:00:00-0700".parse().unwrap(), 
-                end: "2022-12-30T12:00:00-0700".parse().unwrap(), 
-            }, 
-            old: ReservationWindow {
-                rid: "1121".to_string(), 
-                start: "2022-12-25T15:00:00-0700".parse().unwrap(), 
-                end: "2022-12-28T12:00:00-0700".parse().unwrap(), 
-            }
-        });
+    async fn occupancy_snapshot(
+        &self,
+        at: Option<DateTime<Utc>>,
+    ) -> Result<Vec<(ResourceId, abi::Reservation)>, abi::Error> {
+        let at = at.unwrap_or_else(|| self.clock.now());
+        let rsvps = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            SELECT * FROM rsvp.reservations
+            WHERE status = 'confirmed' AND timespan @> $1
+            ORDER BY resource_id
+            "#,
+        )
+        .bind(at)
+        .fetch_all(&self.pool)
+        .await?;
 
-        assert_eq!(err, abi::Error::ConflictReservation(info));
+        Ok(rsvps
+            .into_iter()
+            .map(|rsvp| (rsvp.resource_id.clone(), rsvp))
+            .collect())
     }
 
-    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
-    async fn reserve_change_status_should_work() {
-        let manager = ReservationManager::new(migrated_pool.clone());
-        let rsvp = Reservation::new_pending(
-            "aliceId",
-            "1021",
-            "2023-01-25T15:00:00-0700".parse().unwrap(),
-            "2023-02-25T12:00:00-0700".parse().unwrap(),
-            "hello...",
-        );
-        let rsvp = manager.reserve(rsvp).await.unwrap();
-
-        let res = manager
-            .change_status(rsvp.id)
-            .await
-            .unwrap();
+    async fn cancel(&self, id: ReservationId) -> Result<abi::Reservation, abi::Error> {
+        let id = Uuid::parse_str(&id).map_err(|_| abi::Error::InvalidReservationId(id.clone()))?;
+        let mut tx = self.pool.begin().await?;
+        let rsvp = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            UPDATE rsvp.reservations SET status = 'cancelled', cancelled_at = $2
+            WHERE id = $1 AND status <> 'cancelled' RETURNING *
+            "#,
+        )
+        .bind(id)
+        .bind(self.clock.now())
+        .fetch_one(&mut tx)
+        .await?;
+        write_outbox(&mut tx, OutboxEventKind::Cancelled, &rsvp).await?;
+        tx.commit().await?;
 
-        assert_eq!(res.status, abi::ReservationStatus::Confirmed as i32);
+        Ok(rsvp)
     }
 
-    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
-    async fn reserve_change_status_not_pending_should_do_nothings() {
-        let manager = ReservationManager::new(migrated_pool.clone());
-        let rsvp = Reservation::new_pending(
-            "aliceId",
-            "1021",
-            "2023-01-25T15:00:00-0700".parse().unwrap(),
-            "2023-02-25T12:00:00-0700".parse().unwrap(),
-            "hello...",
+    async fn query_by_cursor(
+        &self,
+        query: abi::ReservationQuery,
+        cursor: Option<ReservationId>,
+        direction: PageDirection,
+    ) -> Result<CursorPage, abi::Error> {
+        query.validate()?;
+
+        let cursor = match cursor {
+            Some(id) => cursor_key(&self.get(id).await?)?,
+            None => None,
+        };
+        let pagesize = if query.pagesize <= 0 {
+            10
+        } else {
+            query.pagesize
+        };
+
+        let (op, order) = match direction {
+            PageDirection::After => (">", "ASC"),
+            PageDirection::Before => ("<", "DESC"),
+        };
+        let sql = format!(
+            "SELECT * FROM rsvp.reservations WHERE {} AND ($12::timestamptz IS NULL OR (lower(timespan), id) {} ($12, $13::uuid)) ORDER BY lower(timespan) {}, id {} LIMIT $14",
+            QUERY_FILTER, op, order, order
         );
-        let rsvp = manager.reserve(rsvp).await.unwrap();
+        let mut rsvps = self
+            .bind_cursor_filter(sqlx::query_as::<_, abi::Reservation>(&sql), &query, cursor)
+            .bind(pagesize as i64 + 1)
+            .fetch_all(&self.pool)
+            .await?;
 
-        let rsvp = manager
-            .change_status(rsvp.id)
-            .await
-            .unwrap();
+        let has_more = rsvps.len() > pagesize as usize;
+        rsvps.truncate(pagesize as usize);
+        if direction == PageDirection::Before {
+            rsvps.reverse();
+        }
 
-        assert_eq!(rsvp.status, abi::ReservationStatus::Confirmed as i32);
+        let (first, last) = match (rsvps.first(), rsvps.last()) {
+            (Some(first), Some(last)) => (first.clone(), last.clone()),
+            _ => return Ok(CursorPage::default()),
+        };
 
-        let ret = manager.change_status(rsvp.id).await.unwrap_err();
+        let (has_prev, has_next) = match direction {
+            PageDirection::After => (
+                self.cursor_exists(&query, "<", cursor_key(&first)?).await?,
+                has_more,
+            ),
+            PageDirection::Before => (
+                has_more,
+                self.cursor_exists(&query, ">", cursor_key(&last)?).await?,
+            ),
+        };
 
-        assert_eq!(ret, abi::Error::NotFound);
+        Ok(CursorPage {
+            rsvps,
+            next: has_next.then_some(last.id),
+            prev: has_prev.then_some(first.id),
+        })
     }
 
-    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
-    async fn update_note_should_work() {
-        let (manager, rsvp) = make_alice_reservation(&migrated_pool.clone()).await;
+    async fn try_reserve(&self, rsvp: abi::Reservation) -> Result<ReserveOutcome, abi::Error> {
+        match self.reserve(rsvp).await {
+            Ok(rsvp) => Ok(ReserveOutcome::Created(rsvp)),
+            Err(abi::Error::ConflictReservation(info)) => Ok(ReserveOutcome::Conflict(info)),
+            Err(e) => abi::ValidationError::try_from(e).map(ReserveOutcome::Invalid),
+        }
+    }
 
-        let rsvp = manager.update_note(rsvp.id, "world.".to_string()).await.unwrap();
+    async fn next_available_per_resource(
+        &self,
+        resources: Vec<ResourceId>,
+        from: DateTime<Utc>,
+        duration: Duration,
+    ) -> Result<HashMap<ResourceId, Option<DateTime<Utc>>>, abi::Error> {
+        if duration <= Duration::zero() {
+            return Err(abi::Error::InvalidTime);
+        }
 
-        assert_eq!(rsvp.note, "world.");
+        let rows: Vec<(String, DateTime<Utc>, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT resource_id, lower(timespan), upper(timespan) FROM rsvp.reservations
+            WHERE resource_id = ANY($1) AND status <> 'cancelled' AND upper(timespan) > $2
+            ORDER BY resource_id, lower(timespan)
+            "#,
+        )
+        .bind(&resources)
+        .bind(from)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut busy: HashMap<String, Vec<(DateTime<Utc>, DateTime<Utc>)>> = HashMap::new();
+        for (rid, start, end) in rows {
+            busy.entry(rid).or_default().push((start, end));
+        }
+
+        Ok(resources
+            .into_iter()
+            .map(|rid| {
+                let windows = busy.remove(&rid).unwrap_or_default();
+                let start = earliest_free_start(&windows, from, duration);
+                (rid, Some(start))
+            })
+            .collect())
     }
 
-    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
-    async fn get_reservation_should_work() {
-        let (manager, rsvp) = make_tyr_reservation(&migrated_pool.clone()).await;
+    async fn add_blackout(
+        &self,
+        resource_id: ResourceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<(), abi::Error> {
+        if resource_id.is_empty() {
+            return Err(abi::Error::InvalidResourceId(resource_id));
+        }
+        if start >= end {
+            return Err(abi::Error::InvalidTime);
+        }
 
-        let res = manager.get(rsvp.id).await.unwrap();
+        sqlx::query("INSERT INTO rsvp.blackouts (resource_id, timespan) VALUES ($1, $2)")
+            .bind(resource_id)
+            .bind(PgRange::from(start..end))
+            .execute(&self.pool)
+            .await?;
 
-        assert_eq!(res.user_id, rsvp.user_id);
+        Ok(())
     }
 
-    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
-    async fn dalete_reservation_should_work() {
-        let (manager, rsvp) = make_tyr_reservation(&migrated_pool.clone()).await;
+    async fn query_etag(&self, query: abi::ReservationQuery) -> Result<String, abi::Error> {
+        let sql = format!(
+            "SELECT md5(coalesce(string_agg(id::text || '@' || updated_at::text, ',' ORDER BY id), '')) FROM {}",
+            QUERY_CALL
+        );
+        let (etag,): (String,) = bind_query(sqlx::query_as(&sql), &query)
+            .fetch_one(&self.pool)
+            .await?;
 
-        let _ = manager.delete(rsvp.id.clone()).await.unwrap();
-        let err = manager.get(rsvp.id).await.unwrap_err();
-        println!("{:?}", err);
+        Ok(etag)
     }
 
-    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
-    async fn query_reservation_should_work() {
-        let (manager, rsvp) = make_tyr_reservation(&migrated_pool.clone()).await;
+    async fn swap(
+        &self,
+        id_a: ReservationId,
+        id_b: ReservationId,
+    ) -> Result<(abi::Reservation, abi::Reservation), abi::Error> {
+        let id_a =
+            Uuid::parse_str(&id_a).map_err(|_| abi::Error::InvalidReservationId(id_a.clone()))?;
+        let id_b =
+            Uuid::parse_str(&id_b).map_err(|_| abi::Error::InvalidReservationId(id_b.clone()))?;
 
-        let query = ReservationQueryBuilder::default()
-            .user_id("tyrId")
-            .resource_id("1021")
-            .start("2022-12-25T15:00:00-0700".parse::<prost_types::Timestamp>().unwrap())
-            .end("2022-12-28T12:00:00-0700".parse::<prost_types::Timestamp>().unwrap())
-            .status(ReservationStatus::Pending)
-            .build().unwrap();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE")
+            .execute(&mut tx)
+            .await?;
+        // the two rows overlap each other's new slot until both are updated
+        sqlx::query("SET CONSTRAINTS rsvp.reservations_conflict DEFERRED")
+            .execute(&mut tx)
+            .await?;
 
-        let rsvps = manager.query(query).await.unwrap();
+        let rows: Vec<(Uuid, String, DateTime<Utc>, DateTime<Utc>, i32)> = sqlx::query_as(
+            r#"
+            SELECT id, resource_id, lower(timespan), upper(timespan), COALESCE(units, 1)
+            FROM rsvp.reservations WHERE id = $1 OR id = $2 FOR UPDATE
+            "#,
+        )
+        .bind(id_a)
+        .bind(id_b)
+        .fetch_all(&mut tx)
+        .await?;
+        let row = |id: Uuid| rows.iter().find(|r| r.0 == id).ok_or(abi::Error::NotFound);
+        let (_, rid_a, start_a, end_a, units_a) = row(id_a)?;
+        let (_, rid_b, start_b, end_b, units_b) = row(id_b)?;
 
-        assert_eq!(rsvps.len(), 1);
-        assert_eq!(rsvps[0], rsvp);
+        let sql = "UPDATE rsvp.reservations SET timespan = $1 WHERE id = $2 RETURNING *";
+        let rsvp_a = sqlx::query_as::<_, abi::Reservation>(sql)
+            .bind(PgRange::from(*start_b..*end_b))
+            .bind(id_a)
+            .fetch_one(&mut tx)
+            .await?;
+        let rsvp_b = sqlx::query_as::<_, abi::Reservation>(sql)
+            .bind(PgRange::from(*start_a..*end_a))
+            .bind(id_b)
+            .fetch_one(&mut tx)
+            .await?;
 
-        let query = ReservationQueryBuilder::default()
-            .user_id("tyrId")
-            .resource_id("1021")
-            .start("2023-01-25T15:00:00-0700".parse::<prost_types::Timestamp>().unwrap())
-            .end("2023-02-28T12:00:00-0700".parse::<prost_types::Timestamp>().unwrap())
-            .status(ReservationStatus::Pending)
-            .build().unwrap();
+        // with both rows in place, each counts against the other's new slot
+        check_slot(&mut tx, rid_a, *start_b, *end_b, *units_a, &[id_a]).await?;
+        check_slot(&mut tx, rid_b, *start_a, *end_a, *units_b, &[id_b]).await?;
 
-        let rsvps1  = manager.query(query).await.unwrap();
-        
-        assert!(rsvps1.is_empty());
-        
-        let _rsvp =  manager.change_status(rsvps[0].id.clone()).await.unwrap();
+        tx.commit().await?;
 
-        let query = ReservationQueryBuilder::default()
-            .user_id("tyrId")
-            .resource_id("1021")
-            .start("2022-12-25T15:00:00-0700".parse::<prost_types::Timestamp>().unwrap())
-            .end("2022-12-28T12:00:00-0700".parse::<prost_types::Timestamp>().unwrap())
-            .status(ReservationStatus::Pending)
-            .build().unwrap();
+        Ok((rsvp_a, rsvp_b))
+    }
+
+    async fn reserve_with_id(
+        &self,
+        id: ReservationId,
+        mut rsvp: abi::Reservation,
+    ) -> Result<abi::Reservation, abi::Error> {
+        Uuid::parse_str(&id).map_err(|_| abi::Error::InvalidReservationId(id.clone()))?;
+        rsvp.id = id;
 
-        let rsvps1  = manager.query(query).await.unwrap();
+        let mut tx = self.pool.begin().await?;
+        let rsvp = self.insert(&mut tx, rsvp).await?;
+        self.commit_reserved(tx, std::slice::from_ref(&rsvp))
+            .await?;
 
-        assert!(rsvps1.is_empty());
+        Ok(rsvp)
     }
 
+    async fn duration_class_counts(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> Result<HashMap<abi::DurationClass, i64>, abi::Error> {
+        query.validate()?;
 
-    async fn make_tyr_reservation(pool: &PgPool) -> (ReservationManager, Reservation) {
-        make_reservation(
-            pool,
-            "tyrId",
-            "1021",
-            "2022-12-25T15:00:00-0700",
-            "2022-12-28T12:00:00-0700",
-            "hahaha..."
+        let sql = format!(
+            "SELECT rsvp.duration_class(timespan), count(*) FROM rsvp.reservations WHERE {} GROUP BY 1",
+            QUERY_FILTER
+        );
+        let rows: Vec<(i32, i64)> = bind_filter(sqlx::query_as(&sql), &query)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(class, count)| DurationClass::from_i32(class).map(|c| (c, count)))
+            .collect())
+    }
+
+    async fn ending_soon(
+        &self,
+        within: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<abi::Reservation>, abi::Error> {
+        let rsvps = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            SELECT * FROM rsvp.reservations
+            WHERE status = 'confirmed' AND upper(timespan) >= $1 AND upper(timespan) < $2
+            ORDER BY upper(timespan), id
+            "#,
         )
-        .await
+        .bind(now)
+        .bind(now + within)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rsvps)
     }
 
-    async fn make_alice_reservation(pool: &PgPool) -> (ReservationManager, Reservation) {
-        make_reservation(
-            pool,
-            "aliceId",
-            "1021",
-            "2023-01-25T15:00:00-0700",
-            "2023-02-25T12:00:00-0700",
-            "hello..."
+    async fn pending_starting_soon(
+        &self,
+        within: Duration,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<abi::Reservation>, abi::Error> {
+        let rsvps = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            SELECT * FROM rsvp.reservations
+            WHERE status = 'pending' AND lower(timespan) BETWEEN $1 AND $2
+            ORDER BY lower(timespan), id
+            "#,
         )
-        .await
+        .bind(now)
+        .bind(now + within)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rsvps)
     }
 
-    async fn make_reservation(
-        pool: &PgPool, 
-        uid: &str, 
-        rid: &str, 
-        start: &str, 
-        end: &str, 
-        note: &str
-    ) -> (ReservationManager, Reservation) {
-        let manager = ReservationManager::new(pool.clone());
-        let rsvp = Reservation::new_pending(
-            uid, rid, start.parse().unwrap(), end.parse().unwrap(), note
-        );
+    async fn get_by_reference(&self, reference: i64) -> Result<abi::Reservation, abi::Error> {
+        let rsvp = sqlx::query_as::<_, abi::Reservation>(
+            "SELECT * FROM rsvp.reservations WHERE reference = $1",
+        )
+        .bind(reference)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(rsvp)
+    }
+
+    async fn set_capacity(&self, resource_id: ResourceId, capacity: i32) -> Result<(), abi::Error> {
+        if resource_id.is_empty() {
+            return Err(abi::Error::InvalidResourceId(resource_id));
+        }
+        if capacity < 1 {
+            return Err(abi::Error::InvalidCapacity(capacity));
+        }
+
+        let mut tx = self.pool.begin().await?;
+        // keep reservations from being added while the existing ones are checked
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('resource:' || $1))")
+            .bind(&resource_id)
+            .execute(&mut tx)
+            .await?;
+
+        let spans: Vec<(DateTime<Utc>, DateTime<Utc>, i32)> = sqlx::query_as(
+            r#"
+            SELECT lower(timespan), upper(timespan), COALESCE(units, 1) FROM rsvp.reservations
+            WHERE resource_id = $1 AND status <> 'cancelled'
+            "#,
+        )
+        .bind(&resource_id)
+        .fetch_all(&mut tx)
+        .await?;
+        let first = spans.iter().map(|(s, _, _)| *s).min();
+        let last = spans.iter().map(|(_, e, _)| *e).max();
+        if let Some((start, end)) = first.zip(last) {
+            let used = split_by_edges(start, end, &spans)
+                .into_iter()
+                .map(|(_, _, used)| used)
+                .max()
+                .unwrap_or(0);
+            if used > capacity {
+                return Err(abi::Error::CapacityExceeded {
+                    resource_id,
+                    capacity,
+                });
+            }
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO rsvp.resources (id, capacity) VALUES ($1, $2)
+            ON CONFLICT (id) DO UPDATE SET capacity = EXCLUDED.capacity
+            "#,
+        )
+        .bind(&resource_id)
+        .bind(capacity)
+        .execute(&mut tx)
+        .await?;
+        // only exclusive rows are covered by the conflict constraint
+        sqlx::query(
+            r#"
+            UPDATE rsvp.reservations SET shared = $2
+            WHERE resource_id = $1 AND status <> 'cancelled' AND shared <> $2
+            "#,
+        )
+        .bind(&resource_id)
+        .bind(capacity > 1)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn availability_for(
+        &self,
+        resource_id: ResourceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        cancel: Option<CancellationToken>,
+    ) -> Result<Vec<SlotAvailability>, abi::Error> {
+        if start >= end {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        let pid = backend_pid(&mut conn, cancel.as_ref()).await?;
+        let (capacity, spans) = cancellable(&self.pool, pid, cancel.as_ref(), async {
+            let capacity = capacity_of(&mut conn, &resource_id).await?;
+            let spans = booked_spans(&mut conn, &resource_id, start, end, &[]).await?;
+            Ok::<_, abi::Error>((capacity, spans))
+        })
+        .await?;
+
+        Ok(split_by_edges(start, end, &spans)
+            .into_iter()
+            .map(|(start, end, used)| SlotAvailability {
+                start,
+                end,
+                used,
+                capacity,
+            })
+            .collect())
+    }
+
+    async fn add_pricing_rule(
+        &self,
+        resource_id: ResourceId,
+        rule: PricingRule,
+    ) -> Result<(), abi::Error> {
+        if resource_id.is_empty() {
+            return Err(abi::Error::InvalidResourceId(resource_id));
+        }
+        if rule.start_hour >= rule.end_hour || rule.end_hour > 24 {
+            return Err(abi::Error::InvalidPricingRule(format!(
+                "hours {}..{}",
+                rule.start_hour, rule.end_hour
+            )));
+        }
+        if rule.rate_cents < 0 {
+            return Err(abi::Error::InvalidPricingRule(format!(
+                "rate {}",
+                rule.rate_cents
+            )));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO rsvp.pricing_rules (resource_id, weekday, start_hour, end_hour, rate_cents)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(resource_id)
+        .bind(rule.weekday.map(|d| d.num_days_from_sunday() as i16))
+        .bind(rule.start_hour as i16)
+        .bind(rule.end_hour as i16)
+        .bind(rule.rate_cents)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn quote(
+        &self,
+        resource_id: ResourceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<i64, abi::Error> {
+        if start >= end {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let rows: Vec<(Option<i16>, i16, i16, i64)> = sqlx::query_as(
+            r#"
+            SELECT weekday, start_hour, end_hour, rate_cents FROM rsvp.pricing_rules
+            WHERE resource_id = $1 ORDER BY weekday NULLS LAST, id
+            "#,
+        )
+        .bind(&resource_id)
+        .fetch_all(&self.pool)
+        .await?;
+        let rules: Vec<PricingRule> = rows
+            .into_iter()
+            .map(|(weekday, start_hour, end_hour, rate_cents)| PricingRule {
+                weekday: weekday.map(weekday_from_sunday),
+                start_hour: start_hour as u32,
+                end_hour: end_hour as u32,
+                rate_cents,
+            })
+            .collect();
+
+        // walk the window hour by hour, accumulating rate * seconds
+        let mut total = 0i64;
+        let mut cursor = start;
+        while cursor < end {
+            let next = (start_of_hour(cursor) + Duration::hours(1)).min(end);
+            if let Some(rule) = rules.iter().find(|r| r.applies_to(cursor)) {
+                total += rule.rate_cents * (next - cursor).num_seconds();
+            }
+            cursor = next;
+        }
+
+        Ok(total / 3600)
+    }
+
+    async fn modified_by(
+        &self,
+        actor_id: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Vec<abi::Reservation>, abi::Error> {
+        let rsvps = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            SELECT * FROM rsvp.reservations WHERE id IN (
+                SELECT reservation_id FROM rsvp.reservation_changes
+                WHERE actor_id = $1 AND op = 'update' AND ($2::timestamptz IS NULL OR changed_at >= $2)
+            )
+            ORDER BY lower(timespan), id
+            "#,
+        )
+        .bind(actor_id)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rsvps)
+    }
+
+    async fn reserve_bundle(
+        &self,
+        user_id: UserId,
+        resource_ids: Vec<ResourceId>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        note: String,
+    ) -> Result<Vec<abi::Reservation>, abi::Error> {
+        let bundle_id = Uuid::new_v4().to_string();
+
+        let mut tx = self.pool.begin().await?;
+        let mut rsvps = Vec::with_capacity(resource_ids.len());
+        for rid in resource_ids {
+            let mut rsvp =
+                abi::Reservation::new_pending(&user_id, rid, start.into(), end.into(), &note);
+            rsvp.bundle_id = bundle_id.clone();
+            match self.insert(&mut tx, rsvp).await {
+                Ok(rsvp) => rsvps.push(rsvp),
+                Err(e) => {
+                    self.release_external(&rsvps).await;
+                    return Err(e);
+                }
+            }
+        }
+        self.commit_reserved(tx, &rsvps).await?;
+
+        Ok(rsvps)
+    }
+
+    async fn get_bundle(&self, bundle_id: String) -> Result<Vec<abi::Reservation>, abi::Error> {
+        let id = Uuid::parse_str(&bundle_id)
+            .map_err(|_| abi::Error::InvalidReservationId(bundle_id.clone()))?;
+        let rsvps = sqlx::query_as::<_, abi::Reservation>(
+            "SELECT * FROM rsvp.reservations WHERE bundle_id = $1 ORDER BY resource_id",
+        )
+        .bind(id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rsvps)
+    }
+
+    async fn relative(
+        &self,
+        spec: RelativeWindow,
+        now: Option<DateTime<Utc>>,
+        mut filter: abi::ReservationQuery,
+    ) -> Result<Vec<abi::Reservation>, abi::Error> {
+        let (start, end) = spec.resolve(now.unwrap_or_else(|| self.clock.now()));
+        filter.start = Some(abi::convert_to_timestamp(start));
+        filter.end = Some(abi::convert_to_timestamp(end));
+
+        self.query(filter).await
+    }
+
+    async fn first(
+        &self,
+        mut query: abi::ReservationQuery,
+    ) -> Result<Option<abi::Reservation>, abi::Error> {
+        query.page = 1;
+        query.pagesize = 1;
+
+        Ok(self.query(query).await?.into_iter().next())
+    }
+
+    async fn last(
+        &self,
+        mut query: abi::ReservationQuery,
+    ) -> Result<Option<abi::Reservation>, abi::Error> {
+        query.desc = !query.desc;
+
+        self.first(query).await
+    }
+
+    async fn reassign_resource(
+        &self,
+        from: &str,
+        to: &str,
+        window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    ) -> Result<u64, abi::Error> {
+        if to.is_empty() || to == from {
+            return Err(abi::Error::InvalidResourceId(to.to_string()));
+        }
+
+        let window = window.map(|(start, end)| PgRange::from(start..end));
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('resource:' || $1))")
+            .bind(to)
+            .execute(&mut tx)
+            .await?;
+
+        let active: Vec<(Uuid, DateTime<Utc>, DateTime<Utc>, i32)> = sqlx::query_as(
+            r#"
+            SELECT id, lower(timespan), upper(timespan), COALESCE(units, 1) FROM rsvp.reservations
+            WHERE resource_id = $1 AND ($2::tstzrange IS NULL OR $2 @> timespan)
+                AND status <> 'cancelled'
+            ORDER BY lower(timespan), id FOR UPDATE
+            "#,
+        )
+        .bind(from)
+        .bind(window.clone())
+        .fetch_all(&mut tx)
+        .await?;
+        // moved one by one, so each is checked against those already moved
+        for (id, start, end, units) in &active {
+            let shared = check_slot(&mut tx, to, *start, *end, *units, &[])
+                .await
+                .map_err(|e| match e {
+                    abi::Error::ResourceUnavailable { .. }
+                    | abi::Error::CapacityExceeded { .. } => abi::Error::ConflictReservation(
+                        ReservationConflictInfo::Unparsed(e.to_string()),
+                    ),
+                    e => e,
+                })?;
+            sqlx::query("UPDATE rsvp.reservations SET resource_id = $2, shared = $3 WHERE id = $1")
+                .bind(id)
+                .bind(to)
+                .bind(shared)
+                .execute(&mut tx)
+                .await?;
+        }
+        let cancelled = sqlx::query(
+            r#"
+            UPDATE rsvp.reservations SET resource_id = $2
+            WHERE resource_id = $1 AND ($3::tstzrange IS NULL OR $3 @> timespan)
+                AND status = 'cancelled'
+            "#,
+        )
+        .bind(from)
+        .bind(to)
+        .bind(window)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(active.len() as u64 + cancelled.rows_affected())
+    }
+
+    async fn calendar_events(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> Result<Vec<CalendarEvent>, abi::Error> {
+        self.query(query)
+            .await?
+            .into_iter()
+            .map(CalendarEvent::try_from)
+            .collect()
+    }
+
+    async fn recurring_availability(
+        &self,
+        resource_id: ResourceId,
+        rule: RecurrenceRule,
+        count: u32,
+    ) -> Result<Vec<((DateTime<Utc>, DateTime<Utc>), bool)>, abi::Error> {
+        if rule.duration <= Duration::zero() {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        let capacity = capacity_of(&mut conn, &resource_id).await?;
+        let mut ret = Vec::with_capacity(count as usize);
+        for (start, end) in rule.occurrences(count) {
+            let blocked: bool = sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM rsvp.blackouts WHERE resource_id = $1 AND timespan && $2)",
+            )
+            .bind(&resource_id)
+            .bind(PgRange::from(start..end))
+            .fetch_one(&mut conn)
+            .await?;
+            if blocked {
+                ret.push(((start, end), false));
+                continue;
+            }
+
+            let spans = booked_spans(&mut conn, &resource_id, start, end, &[]).await?;
+            let used = split_by_edges(start, end, &spans)
+                .into_iter()
+                .map(|(_, _, used)| used)
+                .max()
+                .unwrap_or(0);
+            ret.push(((start, end), used < capacity));
+        }
+
+        Ok(ret)
+    }
+
+    async fn drain_outbox(&self, limit: i64) -> Result<Vec<OutboxEvent>, abi::Error> {
+        let rows: Vec<(i64, String, Vec<u8>, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            DELETE FROM rsvp.outbox WHERE id IN (
+                SELECT id FROM rsvp.outbox ORDER BY id LIMIT $1 FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, kind, payload, created_at
+            "#,
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut events = rows
+            .into_iter()
+            .map(|(id, kind, payload, created_at)| {
+                Ok(OutboxEvent {
+                    id,
+                    kind: kind.parse()?,
+                    reservation: abi::Reservation::decode(&payload)?,
+                    created_at,
+                })
+            })
+            .collect::<Result<Vec<_>, abi::Error>>()?;
+        events.sort_by_key(|event| event.id);
+
+        Ok(events)
+    }
+
+    async fn utilization(
+        &self,
+        resource_id: ResourceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        cancel: Option<CancellationToken>,
+    ) -> Result<f64, abi::Error> {
+        if start >= end {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        let pid = backend_pid(&mut conn, cancel.as_ref()).await?;
+        let (capacity, spans) = cancellable(&self.pool, pid, cancel.as_ref(), async {
+            let capacity = capacity_of(&mut conn, &resource_id).await?;
+            let spans: Vec<(DateTime<Utc>, DateTime<Utc>, i32)> = sqlx::query_as(
+                r#"
+                SELECT lower(timespan), upper(timespan), COALESCE(units, 1) FROM rsvp.reservations
+                WHERE resource_id = $1 AND timespan && $2 AND status = 'confirmed'
+                "#,
+            )
+            .bind(&resource_id)
+            .bind(PgRange::from(start..end))
+            .fetch_all(&mut conn)
+            .await?;
+            Ok::<_, abi::Error>((capacity, spans))
+        })
+        .await?;
+
+        // segments lie within the window, so bookings are clamped to it
+        let booked: i64 = split_by_edges(start, end, &spans)
+            .into_iter()
+            .map(|(s, e, used)| (e - s).num_seconds() * i64::from(used.min(capacity)))
+            .sum();
+
+        Ok(booked as f64 / ((end - start).num_seconds() * i64::from(capacity)) as f64)
+    }
+
+    async fn hold(
+        &self,
+        mut rsvp: abi::Reservation,
+        ttl: Duration,
+    ) -> Result<abi::Reservation, abi::Error> {
+        if ttl <= Duration::zero() {
+            return Err(abi::Error::InvalidTime);
+        }
+        rsvp.status = ReservationStatus::Pending as i32;
+        rsvp.expires_at = Some(abi::convert_to_timestamp(self.clock.now() + ttl));
+
+        let rsvp = self.reserve(rsvp).await?;
+        self.emit(ReservationEventKind::Held, &rsvp);
+        Ok(rsvp)
+    }
+
+    async fn expire_holds(&self) -> Result<Vec<abi::Reservation>, abi::Error> {
+        let mut tx = self.pool.begin().await?;
+        let rsvps = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            UPDATE rsvp.reservations SET status = 'cancelled', cancelled_at = $1
+            WHERE status = 'pending' AND expires_at <= $1 RETURNING *
+            "#,
+        )
+        .bind(self.clock.now())
+        .fetch_all(&mut tx)
+        .await?;
+        for rsvp in &rsvps {
+            write_outbox(&mut tx, OutboxEventKind::Cancelled, rsvp).await?;
+        }
+        tx.commit().await?;
+        for rsvp in &rsvps {
+            self.emit(ReservationEventKind::HoldExpired, rsvp);
+        }
+
+        Ok(rsvps)
+    }
+
+    async fn user_schedule_conflicts(
+        &self,
+        user_id: UserId,
+    ) -> Result<Vec<(abi::Reservation, abi::Reservation)>, abi::Error> {
+        let mut conn = self.pool.acquire().await?;
+        let pairs: Vec<(Uuid, Uuid)> = sqlx::query_as(
+            r#"
+            SELECT a.id, b.id FROM rsvp.reservations a
+            JOIN rsvp.reservations b
+            ON a.user_id = b.user_id AND a.id < b.id AND a.timespan && b.timespan
+            WHERE a.user_id = $1 AND a.status <> 'cancelled' AND b.status <> 'cancelled'
+            ORDER BY lower(a.timespan), a.id, lower(b.timespan), b.id
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&mut conn)
+        .await?;
+
+        load_pairs(&mut conn, "rsvp.reservations", pairs).await
+    }
+
+    async fn distinct_user_count(
+        &self,
+        resource_id: ResourceId,
+        window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+        include_blocked: bool,
+    ) -> Result<i64, abi::Error> {
+        let window = window.map(|(start, end)| PgRange::from(start..end));
+        let count = sqlx::query_scalar(
+            r#"
+            SELECT count(DISTINCT user_id) FROM rsvp.reservations
+            WHERE resource_id = $1 AND ($2::tstzrange IS NULL OR $2 @> timespan)
+            AND status <> 'cancelled' AND ($3 OR status <> 'blocked')
+            "#,
+        )
+        .bind(resource_id)
+        .bind(window)
+        .bind(include_blocked)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(count)
+    }
+
+    async fn revenue(&self, query: abi::ReservationQuery) -> Result<i64, abi::Error> {
+        query.validate()?;
+
+        // only confirmed bookings earn anything, whatever status the query asks for
+        let query = abi::ReservationQuery {
+            status: ReservationStatus::Confirmed as i32,
+            ..query
+        };
+        let sql = format!(
+            "SELECT COALESCE(sum(amount_cents), 0)::bigint FROM rsvp.reservations WHERE {}",
+            QUERY_FILTER
+        );
+        let (revenue,): (i64,) = bind_filter(sqlx::query_as(&sql), &query)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(revenue)
+    }
+
+    async fn find_incomplete(
+        &self,
+        field: RequiredField,
+    ) -> Result<Vec<abi::Reservation>, abi::Error> {
+        let sql = format!(
+            "SELECT * FROM rsvp.reservations WHERE {} IS NULL ORDER BY lower(timespan), id",
+            field.column()
+        );
+        let rsvps = sqlx::query_as::<_, abi::Reservation>(&sql)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rsvps)
+    }
+
+    async fn user_free_gaps(
+        &self,
+        user_id: UserId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        cancel: Option<CancellationToken>,
+    ) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>, abi::Error> {
+        if start >= end {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        let pid = backend_pid(&mut conn, cancel.as_ref()).await?;
+        let spans: Vec<(DateTime<Utc>, DateTime<Utc>)> = cancellable(
+            &self.pool,
+            pid,
+            cancel.as_ref(),
+            sqlx::query_as(
+                r#"
+                SELECT lower(timespan), upper(timespan) FROM rsvp.reservations
+                WHERE user_id = $1 AND timespan && $2 AND status <> 'cancelled'
+                ORDER BY lower(timespan)
+                "#,
+            )
+            .bind(user_id)
+            .bind(PgRange::from(start..end))
+            .fetch_all(&mut conn),
+        )
+        .await?;
+
+        let mut gaps = vec![];
+        let mut cursor = start;
+        // spans are sorted by start, so overlapping ones merge as the cursor advances
+        for (s, e) in spans {
+            if s > cursor {
+                gaps.push((cursor, s));
+            }
+            cursor = cursor.max(e);
+        }
+        if cursor < end {
+            gaps.push((cursor, end));
+        }
+
+        Ok(gaps)
+    }
+
+    async fn reserve_validated(
+        &self,
+        rsvp: abi::Reservation,
+    ) -> Result<abi::Reservation, abi::Error> {
+        rsvp.validate_all()
+            .map_err(abi::Error::InvalidReservation)?;
+
+        self.reserve(rsvp).await
+    }
+
+    async fn compact_schedule(
+        &self,
+        resource_id: ResourceId,
+        from: DateTime<Utc>,
+    ) -> Result<Vec<abi::Reservation>, abi::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('resource:' || $1))")
+            .bind(&resource_id)
+            .execute(&mut tx)
+            .await?;
+
+        let movable: Vec<(Uuid, DateTime<Utc>, DateTime<Utc>, i32)> = sqlx::query_as(
+            r#"
+            SELECT id, lower(timespan), upper(timespan), COALESCE(units, 1) FROM rsvp.reservations
+            WHERE resource_id = $1 AND status = 'confirmed' AND lower(timespan) >= $2
+            ORDER BY lower(timespan), id FOR UPDATE
+            "#,
+        )
+        .bind(&resource_id)
+        .bind(from)
+        .fetch_all(&mut tx)
+        .await?;
+        // everything else still occupying the resource after `from`, blackouts included
+        let fixed: Vec<(DateTime<Utc>, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT lower(timespan), upper(timespan) FROM rsvp.reservations
+            WHERE resource_id = $1 AND status <> 'cancelled' AND upper(timespan) > $2
+                AND NOT (status = 'confirmed' AND lower(timespan) >= $2)
+            UNION ALL
+            SELECT lower(timespan), upper(timespan) FROM rsvp.blackouts
+            WHERE resource_id = $1 AND upper(timespan) > $2
+            ORDER BY 1
+            "#,
+        )
+        .bind(&resource_id)
+        .bind(from)
+        .fetch_all(&mut tx)
+        .await?;
+
+        let mut rsvps = Vec::with_capacity(movable.len());
+        let mut cursor = from;
+        for (id, start, end, units) in movable {
+            let duration = end - start;
+            let new_start = earliest_free_start(&fixed, cursor, duration).min(start);
+            let new_end = new_start + duration;
+            check_slot(&mut tx, &resource_id, new_start, new_end, units, &[id]).await?;
+            let rsvp: abi::Reservation = sqlx::query_as(
+                "UPDATE rsvp.reservations SET timespan = $1 WHERE id = $2 RETURNING *",
+            )
+            .bind(PgRange::from(new_start..new_end))
+            .bind(id)
+            .fetch_one(&mut tx)
+            .await?;
+            rsvps.push(rsvp);
+            cursor = new_end;
+        }
+
+        tx.commit().await?;
+
+        Ok(rsvps)
+    }
+
+    async fn update_if_version(
+        &self,
+        id: ReservationId,
+        expected_version: i64,
+        changes: ReservationChanges,
+    ) -> Result<abi::Reservation, abi::Error> {
+        let uuid =
+            Uuid::parse_str(&id).map_err(|_| abi::Error::InvalidReservationId(id.clone()))?;
+        if let Some((start, end)) = changes.window {
+            if start >= end {
+                return Err(abi::Error::InvalidTime);
+            }
+        }
+        if let Some(note) = &changes.note {
+            if note.chars().count() > abi::MAX_NOTE_LEN {
+                return Err(abi::Error::NoteTooLong {
+                    max: abi::MAX_NOTE_LEN,
+                });
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let current: abi::Reservation =
+            sqlx::query_as("SELECT * FROM rsvp.reservations WHERE id = $1 FOR UPDATE")
+                .bind(uuid)
+                .fetch_one(&mut tx)
+                .await?;
+        if current.version != expected_version {
+            return Err(abi::Error::VersionConflict {
+                current: current.version,
+            });
+        }
+        if let Some((start, end)) = changes.window {
+            // a moved window has to pass the same checks as a new booking
+            let moved = abi::Reservation {
+                start_time: Some(abi::convert_to_timestamp(start)),
+                end_time: Some(abi::convert_to_timestamp(end)),
+                ..current.clone()
+            };
+            self.check_policies(&moved)?;
+            check_slot(
+                &mut tx,
+                &current.resource_id,
+                start,
+                end,
+                current.units.unwrap_or(1),
+                &[uuid],
+            )
+            .await?;
+        }
+
+        let rsvp = sqlx::query_as(
+            r#"
+            UPDATE rsvp.reservations
+            SET note = COALESCE($2, note), timespan = COALESCE($3, timespan)
+            WHERE id = $1 RETURNING *
+            "#,
+        )
+        .bind(uuid)
+        .bind(changes.note)
+        .bind(changes.window.map(|(start, end)| PgRange::from(start..end)))
+        .fetch_one(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(rsvp)
+    }
+
+    async fn add_to_group(&self, group: String, resource_id: ResourceId) -> Result<(), abi::Error> {
+        if resource_id.is_empty() {
+            return Err(abi::Error::InvalidResourceId(resource_id));
+        }
+
+        sqlx::query(
+            "INSERT INTO rsvp.resource_groups (group_id, resource_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(group)
+        .bind(resource_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reserve_from_group(
+        &self,
+        user_id: UserId,
+        group: String,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        note: String,
+    ) -> Result<abi::Reservation, abi::Error> {
+        let members: Vec<String> = sqlx::query_scalar(
+            "SELECT resource_id FROM rsvp.resource_groups WHERE group_id = $1 ORDER BY resource_id",
+        )
+        .bind(&group)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut err = abi::Error::NotFound;
+        for rid in members {
+            let rsvp =
+                abi::Reservation::new_pending(&user_id, rid, start.into(), end.into(), &note);
+            match self.reserve(rsvp).await {
+                // busy, try the next member
+                Err(e @ abi::Error::ConflictReservation(_))
+                | Err(e @ abi::Error::CapacityExceeded { .. })
+                | Err(e @ abi::Error::ResourceUnavailable { .. }) => err = e,
+                ret => return ret,
+            }
+        }
+
+        // all members were busy, or the group is empty
+        Err(err)
+    }
+
+    async fn heatmap(
+        &self,
+        resource_id: ResourceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        tz: FixedOffset,
+        cancel: Option<CancellationToken>,
+    ) -> Result<Vec<((Weekday, u8), i64)>, abi::Error> {
+        if start >= end {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let mut conn = self.pool.acquire().await?;
+        let pid = backend_pid(&mut conn, cancel.as_ref()).await?;
+        let spans: Vec<(DateTime<Utc>, DateTime<Utc>)> = cancellable(
+            &self.pool,
+            pid,
+            cancel.as_ref(),
+            sqlx::query_as(
+                r#"
+                SELECT lower(timespan), upper(timespan) FROM rsvp.reservations
+                WHERE resource_id = $1 AND timespan && $2 AND status <> 'cancelled'
+                "#,
+            )
+            .bind(&resource_id)
+            .bind(PgRange::from(start..end))
+            .fetch_all(&mut conn),
+        )
+        .await?;
+
+        let mut cells = std::collections::BTreeMap::new();
+        for (lower, upper) in spans {
+            let upper = upper.min(end);
+            let mut hour = start_of_hour(lower.max(start).with_timezone(&tz));
+            while hour < upper {
+                let key = (hour.weekday().num_days_from_monday(), hour.hour() as u8);
+                *cells.entry(key).or_insert(0i64) += 1;
+                hour += Duration::hours(1);
+            }
+        }
+
+        Ok(cells
+            .into_iter()
+            .map(|((day, hour), count)| ((weekday_from_monday(day), hour), count))
+            .collect())
+    }
+
+    fn subscribe_resource(&self, resource_id: ResourceId) -> ReservationEventStream {
+        let receiver = self.events.subscribe();
+        Box::pin(futures::stream::unfold(
+            (receiver, resource_id),
+            |(mut receiver, resource_id)| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(event) if event.reservation.resource_id == resource_id => {
+                            return Some((event, (receiver, resource_id)));
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            },
+        ))
+    }
+
+    async fn search_ranked(
+        &self,
+        term: String,
+        filter: abi::ReservationQuery,
+    ) -> Result<Vec<abi::Reservation>, abi::Error> {
+        filter.validate()?;
+        let term = term.trim();
+        if term.is_empty() {
+            return Err(abi::Error::InvalidQuery("empty search term".into()));
+        }
+
+        let (sql, term) = if term.chars().count() < MIN_RANKED_TERM_LEN {
+            let sql = format!(
+                "SELECT * FROM rsvp.reservations WHERE {} AND note ILIKE $12 ORDER BY lower(timespan), id",
+                QUERY_FILTER
+            );
+            (sql, like_pattern(term))
+        } else {
+            let sql = format!(
+                r#"
+                SELECT * FROM rsvp.reservations
+                WHERE {} AND note_tsv @@ plainto_tsquery('simple', $12)
+                ORDER BY ts_rank(note_tsv, plainto_tsquery('simple', $12)) DESC, lower(timespan), id
+                "#,
+                QUERY_FILTER
+            );
+            (sql, term.to_string())
+        };
+
+        let rsvps = bind_filter(sqlx::query_as::<_, abi::Reservation>(&sql), &filter)
+            .bind(term)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rsvps)
+    }
+
+    async fn at_instant(
+        &self,
+        resource_id: Option<&str>,
+        t: DateTime<Utc>,
+    ) -> Result<Vec<abi::Reservation>, abi::Error> {
+        let rsvps = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            SELECT * FROM rsvp.reservations
+            WHERE ($1::text IS NULL OR resource_id = $1) AND timespan @> $2::timestamptz
+            AND status <> 'cancelled'
+            ORDER BY resource_id, lower(timespan)
+            "#,
+        )
+        .bind(resource_id)
+        .bind(t)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rsvps)
+    }
+
+    async fn reserve_with_shift(
+        &self,
+        rsvp: abi::Reservation,
+        auto_shift: Option<Duration>,
+    ) -> Result<(abi::Reservation, Duration), abi::Error> {
+        let tolerance = auto_shift.unwrap_or_else(Duration::zero);
+        let start = abi::to_datetime(rsvp.start_time.as_ref())?;
+        let end = abi::to_datetime(rsvp.end_time.as_ref())?;
+
+        let step = Duration::minutes(AUTO_SHIFT_STEP_MINUTES);
+        let mut shift = Duration::zero();
+        loop {
+            let shifted = abi::Reservation {
+                start_time: Some(abi::convert_to_timestamp(start + shift)),
+                end_time: Some(abi::convert_to_timestamp(end + shift)),
+                ..rsvp.clone()
+            };
+            match self.reserve(shifted).await {
+                Ok(rsvp) => return Ok((rsvp, shift)),
+                Err(
+                    abi::Error::ConflictReservation(_)
+                    | abi::Error::CapacityExceeded { .. }
+                    | abi::Error::ResourceUnavailable { .. },
+                ) if shift + step <= tolerance => shift = shift + step,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn user_sessions(
+        &self,
+        user_id: UserId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>, abi::Error> {
+        if start >= end {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let spans: Vec<(DateTime<Utc>, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT lower(timespan), upper(timespan) FROM rsvp.reservations
+            WHERE user_id = $1 AND timespan && $2 AND status <> 'cancelled'
+            ORDER BY lower(timespan)
+            "#,
+        )
+        .bind(user_id)
+        .bind(PgRange::from(start..end))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let max_gap = Duration::minutes(SESSION_MAX_GAP_MINUTES);
+        let mut sessions: Vec<(DateTime<Utc>, DateTime<Utc>)> = vec![];
+        for (s, e) in spans {
+            match sessions.last_mut() {
+                Some(last) if s - last.1 <= max_gap => last.1 = last.1.max(e),
+                _ => sessions.push((s, e)),
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    async fn sync_diff(
+        &self,
+        known: Vec<(ReservationId, i64)>,
+        filter: abi::ReservationQuery,
+    ) -> Result<SyncDiff, abi::Error> {
+        filter.validate()?;
+
+        let sql = format!(
+            "SELECT * FROM rsvp.reservations WHERE {} ORDER BY lower(timespan), id",
+            QUERY_FILTER
+        );
+        let rsvps = bind_filter(sqlx::query_as::<_, abi::Reservation>(&sql), &filter)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut known: HashMap<ReservationId, i64> = known.into_iter().collect();
+        let mut diff = SyncDiff::default();
+        for rsvp in rsvps {
+            match known.remove(&rsvp.id) {
+                None => diff.added.push(rsvp),
+                Some(version) if version < rsvp.version => diff.updated.push(rsvp),
+                Some(_) => {}
+            }
+        }
+        diff.removed = known.into_keys().collect();
+        diff.removed.sort();
+
+        Ok(diff)
+    }
+
+    async fn longest_per_resource(
+        &self,
+        window: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    ) -> Result<HashMap<ResourceId, abi::Reservation>, abi::Error> {
+        let window = window.map(|(start, end)| PgRange::from(start..end));
+        let rsvps = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            SELECT DISTINCT ON (resource_id) * FROM rsvp.reservations
+            WHERE status <> 'cancelled' AND ($1::tstzrange IS NULL OR $1 @> timespan)
+            ORDER BY resource_id, upper(timespan) - lower(timespan) DESC, lower(timespan)
+            "#,
+        )
+        .bind(window)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rsvps
+            .into_iter()
+            .map(|rsvp| (rsvp.resource_id.clone(), rsvp))
+            .collect())
+    }
+
+    async fn approve(
+        &self,
+        id: ReservationId,
+        approver_id: &str,
+    ) -> Result<abi::Reservation, abi::Error> {
+        let id = Uuid::parse_str(&id).map_err(|_| abi::Error::InvalidReservationId(id.clone()))?;
+        let mut tx = self.pool.begin().await?;
+        let (assigned, approval): (Option<String>, String) = sqlx::query_as(
+            "SELECT approver_id, approval_status FROM rsvp.reservations WHERE id = $1 FOR UPDATE",
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if approval != "pending" || assigned.as_deref() != Some(approver_id) {
+            return Err(abi::Error::NotAuthorized);
+        }
+
+        sqlx::query("SELECT set_config('rsvp.actor_id', $1, true)")
+            .bind(approver_id)
+            .execute(&mut *tx)
+            .await?;
+        let rsvp = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            UPDATE rsvp.reservations
+            SET status = 'confirmed', approval_status = 'approved', expires_at = NULL
+            WHERE id = $1 AND status = 'pending' RETURNING *
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+        write_outbox(&mut tx, OutboxEventKind::Confirmed, &rsvp).await?;
+        tx.commit().await?;
+
+        Ok(rsvp)
+    }
+
+    async fn monthly_counts(
+        &self,
+        resource_id: Option<&str>,
+        year: i32,
+        tz: FixedOffset,
+    ) -> Result<[i64; 12], abi::Error> {
+        let year_start = |year: i32| {
+            tz.ymd_opt(year, 1, 1)
+                .single()
+                .map(|d| d.and_hms(0, 0, 0).with_timezone(&Utc))
+                .ok_or(abi::Error::InvalidTime)
+        };
+        let start = year_start(year)?;
+        let end = year_start(year + 1)?;
+
+        let rows: Vec<(i32, i64)> = sqlx::query_as(
+            r#"
+            SELECT extract(month FROM (lower(timespan) AT TIME ZONE 'UTC') + make_interval(secs => $4))::integer,
+                count(*)
+            FROM rsvp.reservations
+            WHERE ($1::text IS NULL OR resource_id = $1) AND lower(timespan) >= $2
+                AND lower(timespan) < $3 AND status <> 'cancelled'
+            GROUP BY 1
+            "#,
+        )
+        .bind(resource_id)
+        .bind(start)
+        .bind(end)
+        .bind(f64::from(tz.local_minus_utc()))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut counts = [0; 12];
+        for (month, count) in rows {
+            counts[month as usize - 1] = count;
+        }
+        Ok(counts)
+    }
+
+    async fn reserve_exclusive(
+        &self,
+        mut rsvp: abi::Reservation,
+        buffer: Duration,
+    ) -> Result<(abi::Reservation, Vec<abi::Reservation>), abi::Error> {
+        if buffer <= Duration::zero() {
+            return Err(abi::Error::InvalidTime);
+        }
+        rsvp.id.clear();
+        rsvp.bundle_id.clear();
+        let start = abi::to_datetime(rsvp.start_time.as_ref())?;
+        let end = abi::to_datetime(rsvp.end_time.as_ref())?;
+
+        let buffers = [(start - buffer, start), (end, end + buffer)].map(|(start, end)| {
+            let mut blocked = abi::Reservation::new_pending(
+                &rsvp.user_id,
+                &rsvp.resource_id,
+                start.into(),
+                end.into(),
+                "buffer",
+            );
+            blocked.status = ReservationStatus::Blocked as i32;
+            blocked
+        });
+
+        let mut tx = self.pool.begin().await?;
+        let mut reserved = Vec::with_capacity(3);
+        for rsvp in std::iter::once(rsvp).chain(buffers) {
+            match self.insert(&mut tx, rsvp).await {
+                Ok(rsvp) => reserved.push(rsvp),
+                Err(e) => {
+                    self.release_external(&reserved).await;
+                    return Err(e);
+                }
+            }
+        }
+        self.commit_reserved(tx, &reserved).await?;
+
+        let main = reserved.remove(0);
+        Ok((main, reserved))
+    }
+
+    async fn set_display_name(
+        &self,
+        resource_id: ResourceId,
+        display_name: String,
+    ) -> Result<(), abi::Error> {
+        if resource_id.is_empty() {
+            return Err(abi::Error::InvalidResourceId(resource_id));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO rsvp.resources (id, display_name) VALUES ($1, $2)
+            ON CONFLICT (id) DO UPDATE SET display_name = EXCLUDED.display_name
+            "#,
+        )
+        .bind(resource_id)
+        .bind(display_name)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn query_with_resource(
+        &self,
+        query: abi::ReservationQuery,
+    ) -> Result<Vec<(abi::Reservation, ResourceInfo)>, abi::Error> {
+        let rsvps = self.query(query).await?;
+
+        let mut ids: Vec<&str> = rsvps.iter().map(|r| r.resource_id.as_str()).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let rows: Vec<ResourceRow> = sqlx::query_as(
+            "SELECT id, display_name, capacity, lat, lon FROM rsvp.resources WHERE id = ANY($1)",
+        )
+        .bind(ids)
+        .fetch_all(&self.pool)
+        .await?;
+        let catalog: HashMap<String, ResourceInfo> = rows
+            .into_iter()
+            .map(|row| (row.0.clone(), resource_info(row)))
+            .collect();
+
+        Ok(rsvps
+            .into_iter()
+            .map(|rsvp| {
+                let info = catalog
+                    .get(&rsvp.resource_id)
+                    .cloned()
+                    .unwrap_or_else(|| ResourceInfo::unregistered(rsvp.resource_id.clone()));
+                (rsvp, info)
+            })
+            .collect())
+    }
+
+    async fn short_gaps(
+        &self,
+        resource_id: ResourceId,
+        (start, end): (DateTime<Utc>, DateTime<Utc>),
+        shorter_than: Duration,
+    ) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>)>, abi::Error> {
+        if start >= end {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let spans: Vec<(DateTime<Utc>, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT lower(timespan), upper(timespan) FROM rsvp.reservations
+            WHERE resource_id = $1 AND timespan && $2 AND status = 'confirmed'
+            ORDER BY lower(timespan)
+            "#,
+        )
+        .bind(resource_id)
+        .bind(PgRange::from(start..end))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut gaps = vec![];
+        let mut cursor: Option<DateTime<Utc>> = None;
+        // spans are sorted by start, so overlapping ones merge as the cursor advances
+        for (s, e) in spans {
+            if let Some(c) = cursor {
+                if s > c && s - c < shorter_than {
+                    gaps.push((c, s));
+                }
+            }
+            cursor = Some(cursor.map_or(e, |c| c.max(e)));
+        }
+
+        Ok(gaps)
+    }
+
+    async fn receipt(&self, id: ReservationId, tz: FixedOffset) -> Result<Receipt, abi::Error> {
+        let rsvp = self.get(id).await?;
+        let display_name: Option<String> =
+            sqlx::query_scalar("SELECT display_name FROM rsvp.resources WHERE id = $1")
+                .bind(&rsvp.resource_id)
+                .fetch_optional(&self.pool)
+                .await?
+                .flatten();
+
+        let start = abi::to_datetime(rsvp.start_time.as_ref())?;
+        let end = abi::to_datetime(rsvp.end_time.as_ref())?;
+        Ok(Receipt {
+            resource_name: display_name.unwrap_or_else(|| rsvp.resource_id.clone()),
+            start: start.with_timezone(&tz),
+            end: end.with_timezone(&tz),
+            duration: end - start,
+            status: ReservationStatus::from_i32(rsvp.status).unwrap_or(ReservationStatus::Unknown),
+            amount_cents: rsvp.amount_cents,
+            currency: rsvp.currency,
+            id: rsvp.id,
+            reference: rsvp.reference,
+        })
+    }
+
+    async fn cross_tenant_overlaps(
+        &self,
+    ) -> Result<Vec<(abi::Reservation, abi::Reservation)>, abi::Error> {
+        let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+        let schemas: Vec<String> = self.tenant_schemas.iter().map(|s| quote(s)).collect();
+
+        let mut overlaps = vec![];
+        for (i, a) in schemas.iter().enumerate() {
+            for b in &schemas[i + 1..] {
+                let pairs: Vec<(Uuid, Uuid)> = sqlx::query_as(&format!(
+                    r#"
+                    SELECT a.id, b.id FROM {a}.reservations a
+                    JOIN {b}.reservations b
+                        ON a.resource_id = b.resource_id AND a.timespan && b.timespan
+                    WHERE a.status <> 'cancelled' AND b.status <> 'cancelled'
+                    ORDER BY lower(a.timespan), a.id, b.id
+                    "#
+                ))
+                .fetch_all(&self.pool)
+                .await?;
+
+                let (get_a, get_b) = (
+                    format!("SELECT * FROM {a}.reservations WHERE id = $1"),
+                    format!("SELECT * FROM {b}.reservations WHERE id = $1"),
+                );
+                for (id_a, id_b) in pairs {
+                    let get = |sql, id| {
+                        sqlx::query_as::<_, abi::Reservation>(sql)
+                            .bind(id)
+                            .fetch_one(&self.pool)
+                    };
+                    overlaps.push((get(&get_a, id_a).await?, get(&get_b, id_b).await?));
+                }
+            }
+        }
+
+        Ok(overlaps)
+    }
+
+    async fn query_for_viewer(
+        &self,
+        query: abi::ReservationQuery,
+        viewer_id: UserId,
+    ) -> Result<Vec<ViewerReservation>, abi::Error> {
+        let rsvps = self.query(query).await?;
+        Ok(rsvps
+            .into_iter()
+            .map(|rsvp| ViewerReservation::new(rsvp, &viewer_id))
+            .collect())
+    }
+
+    async fn set_location(
+        &self,
+        resource_id: ResourceId,
+        lat: f64,
+        lon: f64,
+    ) -> Result<(), abi::Error> {
+        if resource_id.is_empty() {
+            return Err(abi::Error::InvalidResourceId(resource_id));
+        }
+        if !(-90.0..=90.0).contains(&lat) || !(-180.0..=180.0).contains(&lon) {
+            return Err(abi::Error::InvalidLocation { lat, lon });
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO rsvp.resources (id, lat, lon) VALUES ($1, $2, $3)
+            ON CONFLICT (id) DO UPDATE SET lat = EXCLUDED.lat, lon = EXCLUDED.lon
+            "#,
+        )
+        .bind(resource_id)
+        .bind(lat)
+        .bind(lon)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn nearest_available(
+        &self,
+        center: (f64, f64),
+        radius_km: f64,
+        (start, end): (DateTime<Utc>, DateTime<Utc>),
+        duration: Duration,
+    ) -> Result<Vec<(ResourceInfo, DateTime<Utc>)>, abi::Error> {
+        if start >= end || duration <= Duration::zero() {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let rows: Vec<ResourceRow> = sqlx::query_as(
+            r#"
+            SELECT id, display_name, capacity, lat, lon FROM rsvp.resources
+            WHERE lat IS NOT NULL AND lon IS NOT NULL
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let mut nearby: Vec<(f64, ResourceInfo)> = rows
+            .into_iter()
+            .map(resource_info)
+            .filter_map(|info| {
+                let distance = haversine_km(center, info.location?);
+                (distance <= radius_km).then_some((distance, info))
+            })
+            .collect();
+        nearby.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let ids: Vec<&str> = nearby.iter().map(|(_, info)| info.id.as_str()).collect();
+        let spans: Vec<(String, DateTime<Utc>, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT resource_id, lower(timespan), upper(timespan) FROM rsvp.reservations
+            WHERE resource_id = ANY($1) AND timespan && $2 AND status <> 'cancelled'
+            UNION ALL
+            SELECT resource_id, lower(timespan), upper(timespan) FROM rsvp.blackouts
+            WHERE resource_id = ANY($1) AND timespan && $2
+            ORDER BY 1, 2
+            "#,
+        )
+        .bind(ids)
+        .bind(PgRange::from(start..end))
+        .fetch_all(&self.pool)
+        .await?;
+        let mut busy: HashMap<String, Vec<(DateTime<Utc>, DateTime<Utc>)>> = HashMap::new();
+        for (rid, s, e) in spans {
+            busy.entry(rid).or_default().push((s, e));
+        }
+
+        Ok(nearby
+            .into_iter()
+            .filter_map(|(_, info)| {
+                let windows = busy.remove(&info.id).unwrap_or_default();
+                let free = earliest_free_start(&windows, start, duration);
+                (free + duration <= end).then_some((info, free))
+            })
+            .collect())
+    }
+
+    async fn cancellation_rate(
+        &self,
+        resource_id: Option<&str>,
+        (start, end): (DateTime<Utc>, DateTime<Utc>),
+    ) -> Result<f64, abi::Error> {
+        if start >= end {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let (cancelled, total): (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT count(*) FILTER (WHERE status = 'cancelled'), count(*)
+            FROM rsvp.reservations
+            WHERE ($1::text IS NULL OR resource_id = $1) AND timespan && $2
+                AND status IN ('confirmed', 'cancelled')
+            "#,
+        )
+        .bind(resource_id)
+        .bind(PgRange::from(start..end))
+        .fetch_one(&self.pool)
+        .await?;
+
+        if total == 0 {
+            return Ok(0.0);
+        }
+        Ok(cancelled as f64 / total as f64)
+    }
+
+    async fn split(
+        &self,
+        id: ReservationId,
+        at: DateTime<Utc>,
+    ) -> Result<(abi::Reservation, abi::Reservation), abi::Error> {
+        let uuid =
+            Uuid::parse_str(&id).map_err(|_| abi::Error::InvalidReservationId(id.clone()))?;
+
+        let mut tx = self.pool.begin().await?;
+        let (start, end): (DateTime<Utc>, DateTime<Utc>) = sqlx::query_as(
+            "SELECT lower(timespan), upper(timespan) FROM rsvp.reservations WHERE id = $1 FOR UPDATE",
+        )
+        .bind(uuid)
+        .fetch_one(&mut tx)
+        .await?;
+        if !(start < at && at < end) {
+            return Err(abi::Error::InvalidTime);
+        }
+
+        let head: abi::Reservation =
+            sqlx::query_as("UPDATE rsvp.reservations SET timespan = $2 WHERE id = $1 RETURNING *")
+                .bind(uuid)
+                .bind(PgRange::from(start..at))
+                .fetch_one(&mut tx)
+                .await?;
+        let tail: abi::Reservation = sqlx::query_as(
+            r#"
+            INSERT INTO rsvp.reservations (
+                user_id, resource_id, timespan, note, status, created_by, shared, cancelled_at,
+                bundle_id, expires_at, amount_cents, currency, party_size, special_requests,
+                approver_id, approval_status, units
+            )
+            SELECT user_id, resource_id, $2, note, status, created_by, shared, cancelled_at,
+                bundle_id, expires_at, amount_cents, currency, party_size, special_requests,
+                approver_id, approval_status, units
+            FROM rsvp.reservations WHERE id = $1
+            RETURNING *
+            "#,
+        )
+        .bind(uuid)
+        .bind(PgRange::from(at..end))
+        .fetch_one(&mut tx)
+        .await?;
+        write_outbox(&mut tx, OutboxEventKind::Reserved, &tail).await?;
+        tx.commit().await?;
+
+        Ok((head, tail))
+    }
+
+    async fn slot_bitmap(
+        &self,
+        resource_id: ResourceId,
+        date: NaiveDate,
+        slot_minutes: u32,
+        tz: FixedOffset,
+    ) -> Result<Vec<bool>, abi::Error> {
+        if slot_minutes == 0 || !MINUTES_PER_DAY.is_multiple_of(slot_minutes) {
+            return Err(abi::Error::InvalidSlotLength(slot_minutes));
+        }
+        let start = tz
+            .from_local_datetime(&date.and_hms(0, 0, 0))
+            .single()
+            .ok_or(abi::Error::InvalidTime)?
+            .with_timezone(&Utc);
+        let end = start + Duration::days(1);
+
+        let spans: Vec<(DateTime<Utc>, DateTime<Utc>)> = sqlx::query_as(
+            r#"
+            SELECT lower(timespan), upper(timespan) FROM rsvp.reservations
+            WHERE resource_id = $1 AND timespan && $2 AND status = 'confirmed'
+            "#,
+        )
+        .bind(resource_id)
+        .bind(PgRange::from(start..end))
+        .fetch_all(&self.pool)
+        .await?;
+
+        let slot = Duration::minutes(slot_minutes.into());
+        Ok((0..(MINUTES_PER_DAY / slot_minutes) as i32)
+            .map(|i| {
+                let (s, e) = (start + slot * i, start + slot * (i + 1));
+                spans.iter().any(|&(lower, upper)| lower < e && s < upper)
+            })
+            .collect())
+    }
+}
+
+const QUERY_CALL: &str =
+    "rsvp.query($1, $2, $3, $4::rsvp.reservation_status, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)";
+
+const REGEX_STATEMENT_TIMEOUT: &str = "5s";
+
+const QUERY_STREAM_BUFFER: usize = 8;
+
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+const AUTO_SHIFT_STEP_MINUTES: i64 = 5;
+
+const SESSION_MAX_GAP_MINUTES: i64 = 5;
+
+/// shorter search terms fall back to substring matching
+const MIN_RANKED_TERM_LEN: usize = 3;
+
+const COPY_CHUNK_SIZE: usize = 64 * 1024;
+
+/// the columns `copy_row` writes, in order
+const IMPORT_COLUMNS: &str = "id, user_id, resource_id, timespan, note, status, created_by, \
+    bundle_id, expires_at, amount_cents, currency, party_size, special_requests, approver_id, \
+    approval_status, units";
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+const CONFLICT_CONSTRAINT: &str = "reservations_conflict";
+
+const ADD_CONFLICT_CONSTRAINT: &str = r#"
+    ALTER TABLE rsvp.reservations ADD CONSTRAINT reservations_conflict
+    EXCLUDE USING gist(resource_id WITH =, timespan WITH &&) WHERE (status <> 'cancelled' AND NOT shared)
+    DEFERRABLE INITIALLY IMMEDIATE
+"#;
+
+fn bind_query<'q, O>(
+    q: QueryAs<'q, Postgres, O, PgArguments>,
+    query: &'q abi::ReservationQuery,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    let status = ReservationStatus::from_i32(query.status).unwrap_or(ReservationStatus::Pending);
+    q.bind(str_to_option(&query.user_id))
+        .bind(str_to_option(&query.resource_id))
+        .bind(query.timespan())
+        .bind(status.to_string())
+        .bind(query.page)
+        .bind(query.desc)
+        .bind(query.pagesize)
+        .bind(query.search.as_deref().and_then(str_to_option))
+        .bind(str_to_option(&query.created_by))
+        .bind(query.note_regex.as_deref().and_then(str_to_option))
+        .bind(duration_class_filter(query.duration_class))
+        .bind(&query.weekdays)
+        .bind(str_to_option(&query.weekday_tz))
+        .bind(query.min_party_size)
+}
+
+fn duration_class_filter(class: i32) -> Option<i32> {
+    match DurationClass::from_i32(class) {
+        None | Some(DurationClass::Unspecified) => None,
+        Some(_) => Some(class),
+    }
+}
+
+/// the WHERE clause `rsvp.query` builds, over `$1` to `$11` as bound by `bind_filter`
+const QUERY_FILTER: &str = r#"
+    ($1::text IS NULL OR user_id = $1) AND ($2::text IS NULL OR resource_id = $2)
+    AND $3 @> timespan AND status = $4::rsvp.reservation_status
+    AND ($5::text IS NULL OR user_id ILIKE $5 OR resource_id ILIKE $5 OR note ILIKE $5)
+    AND ($6::text IS NULL OR created_by = $6)
+    AND ($7::text IS NULL OR note ~ $7)
+    AND ($8::integer IS NULL OR rsvp.duration_class(timespan) = $8)
+    AND (cardinality($9::integer[]) = 0
+        OR extract(dow from lower(timespan) AT TIME ZONE COALESCE($10::text, 'UTC'))::integer = ANY($9))
+    AND ($11::integer IS NULL OR party_size >= $11)
+"#;
+
+/// bind every filter of `query` for `QUERY_FILTER`, further parameters start at `$12`
+fn bind_filter<'q, O>(
+    q: QueryAs<'q, Postgres, O, PgArguments>,
+    query: &'q abi::ReservationQuery,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    let status = ReservationStatus::from_i32(query.status).unwrap_or(ReservationStatus::Pending);
+    q.bind(str_to_option(&query.user_id))
+        .bind(str_to_option(&query.resource_id))
+        .bind(query.timespan())
+        .bind(status.to_string())
+        .bind(
+            query
+                .search
+                .as_deref()
+                .and_then(str_to_option)
+                .map(like_pattern),
+        )
+        .bind(str_to_option(&query.created_by))
+        .bind(query.note_regex.as_deref().and_then(str_to_option))
+        .bind(duration_class_filter(query.duration_class))
+        .bind(&query.weekdays)
+        .bind(str_to_option(&query.weekday_tz))
+        .bind(query.min_party_size)
+}
+
+/// match `term` literally anywhere with `ILIKE`
+fn like_pattern(term: &str) -> String {
+    format!(
+        "%{}%",
+        term.replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    )
+}
+
+type CursorKey = Option<(DateTime<Utc>, Uuid)>;
+
+fn cursor_key(rsvp: &abi::Reservation) -> Result<CursorKey, abi::Error> {
+    let start = abi::to_datetime(rsvp.start_time.as_ref())?;
+    let id =
+        Uuid::parse_str(&rsvp.id).map_err(|_| abi::Error::InvalidReservationId(rsvp.id.clone()))?;
+    Ok(Some((start, id)))
+}
+
+/// id, display name, capacity, latitude, longitude
+type ResourceRow = (String, Option<String>, i32, Option<f64>, Option<f64>);
+
+fn resource_info((id, display_name, capacity, lat, lon): ResourceRow) -> ResourceInfo {
+    ResourceInfo {
+        id,
+        display_name,
+        capacity,
+        location: lat.zip(lon),
+    }
+}
+
+/// great-circle distance between two (latitude, longitude) points in degrees
+fn haversine_km((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (dlat, dlon) = ((lat2 - lat1).to_radians(), (lon2 - lon1).to_radians());
+    let a = (dlat / 2.0).sin().powi(2)
+        + lat1.to_radians().cos() * lat2.to_radians().cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+/// `t` truncated to its local hour. Unlike `DurationRound`, this works past the nanosecond
+/// timestamp range that ends in 2262
+fn start_of_hour<Tz: TimeZone>(t: DateTime<Tz>) -> DateTime<Tz> {
+    let local = t.naive_local();
+    t - Duration::seconds((local.minute() * 60 + local.second()).into())
+        - Duration::nanoseconds(local.nanosecond().into())
+}
+
+/// scan `busy` windows (sorted by start) for the first gap of at least `duration` after `from`
+fn earliest_free_start(
+    busy: &[(DateTime<Utc>, DateTime<Utc>)],
+    from: DateTime<Utc>,
+    duration: Duration,
+) -> DateTime<Utc> {
+    let mut cursor = from;
+    for &(start, end) in busy {
+        if start - cursor >= duration {
+            break;
+        }
+        cursor = cursor.max(end);
+    }
+    cursor
+}
+
+async fn verify_conflict_constraint(conn: &mut PgConnection) -> Result<(), abi::Error> {
+    let exists: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM pg_constraint
+            WHERE conrelid = 'rsvp.reservations'::regclass AND conname = $1 AND contype = 'x'
+        )
+        "#,
+    )
+    .bind(CONFLICT_CONSTRAINT)
+    .fetch_one(conn)
+    .await?;
+
+    if !exists {
+        return Err(abi::Error::MissingConstraint(
+            CONFLICT_CONSTRAINT.to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn find_overlaps(
+    conn: &mut PgConnection,
+) -> Result<Vec<(abi::Reservation, abi::Reservation)>, abi::Error> {
+    let pairs: Vec<(Uuid, Uuid)> = sqlx::query_as(
+        r#"
+        SELECT a.id, b.id FROM rsvp.reservations a
+        JOIN rsvp.reservations b
+        ON a.resource_id = b.resource_id AND a.id < b.id AND a.timespan && b.timespan
+        WHERE a.status <> 'cancelled' AND b.status <> 'cancelled'
+        AND NOT (a.shared AND b.shared)
+        ORDER BY lower(a.timespan), a.id, lower(b.timespan), b.id
+        "#,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    load_pairs(conn, "rsvp.reservations", pairs).await
+}
+
+/// fetch the reservations of each id pair from the `rows` table expression, keeping the pairs' order
+async fn load_pairs(
+    conn: &mut PgConnection,
+    rows: &str,
+    pairs: Vec<(Uuid, Uuid)>,
+) -> Result<Vec<(abi::Reservation, abi::Reservation)>, abi::Error> {
+    if pairs.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let ids: Vec<Uuid> = pairs.iter().flat_map(|(a, b)| [*a, *b]).collect();
+    let rsvps: HashMap<String, abi::Reservation> =
+        sqlx::query_as::<_, abi::Reservation>(&format!("SELECT * FROM {rows} WHERE id = ANY($1)"))
+            .bind(ids)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|rsvp| (rsvp.id.clone(), rsvp))
+            .collect();
+
+    Ok(pairs
+        .into_iter()
+        .filter_map(|(a, b)| {
+            let a = rsvps.get(&a.to_string())?.clone();
+            let b = rsvps.get(&b.to_string())?.clone();
+            Some((a, b))
+        })
+        .collect())
+}
+
+/// one CSV line of `IMPORT_COLUMNS` for `bulk_import`'s `COPY`
+fn copy_row(rsvp: &abi::Reservation) -> Result<String, abi::Error> {
+    rsvp.validate()?;
+    let uuid = |id: &str| {
+        Uuid::parse_str(id).map_err(|_| abi::Error::InvalidReservationId(id.to_string()))
+    };
+    let id = match rsvp.id.as_str() {
+        "" => Uuid::new_v4(),
+        id => uuid(id)?,
+    };
+    let status = ReservationStatus::from_i32(rsvp.status).unwrap_or(ReservationStatus::Pending);
+    let approval =
+        ApprovalStatus::from_i32(rsvp.approval_status).unwrap_or(ApprovalStatus::NotRequired);
+    let timespan = format!(
+        "[{},{})",
+        abi::to_datetime(rsvp.start_time.as_ref())?.to_rfc3339(),
+        abi::to_datetime(rsvp.end_time.as_ref())?.to_rfc3339()
+    );
+    let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+    // an unquoted empty field is read as NULL
+    let nullable = |s: &str| match s {
+        "" => String::new(),
+        s => quote(s),
+    };
+    let number = |v: Option<i64>| v.map(|v| v.to_string()).unwrap_or_default();
+    let bundle_id = match rsvp.bundle_id.as_str() {
+        "" => String::new(),
+        id => uuid(id)?.to_string(),
+    };
+    let expires_at = match rsvp.expires_at.as_ref() {
+        Some(_) => abi::to_datetime(rsvp.expires_at.as_ref())?.to_rfc3339(),
+        None => String::new(),
+    };
+    let special_requests = rsvp
+        .special_requests
+        .iter()
+        .map(|r| format!("\"{}\"", r.replace('\\', "\\\\").replace('"', "\\\"")))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    Ok(format!(
+        "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\n",
+        id,
+        quote(&rsvp.user_id),
+        quote(&rsvp.resource_id),
+        quote(&timespan),
+        quote(&rsvp.note),
+        status,
+        nullable(&rsvp.created_by),
+        bundle_id,
+        expires_at,
+        number(rsvp.amount_cents),
+        nullable(rsvp.currency.as_deref().unwrap_or_default()),
+        number(rsvp.party_size.map(i64::from)),
+        quote(&format!("{{{}}}", special_requests)),
+        nullable(&rsvp.approver_id),
+        approval,
+        number(rsvp.units.map(i64::from)),
+    ))
+}
+
+async fn write_outbox(
+    conn: &mut PgConnection,
+    kind: OutboxEventKind,
+    rsvp: &abi::Reservation,
+) -> Result<(), abi::Error> {
+    let id =
+        Uuid::parse_str(&rsvp.id).map_err(|_| abi::Error::InvalidReservationId(rsvp.id.clone()))?;
+    sqlx::query("INSERT INTO rsvp.outbox (reservation_id, kind, payload) VALUES ($1, $2, $3)")
+        .bind(id)
+        .bind(kind.as_str())
+        .bind(rsvp.encode_to_vec())
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+async fn capacity_of(conn: &mut PgConnection, resource_id: &str) -> Result<i32, abi::Error> {
+    let capacity: Option<i32> =
+        sqlx::query_scalar("SELECT capacity FROM rsvp.resources WHERE id = $1")
+            .bind(resource_id)
+            .fetch_optional(conn)
+            .await?;
+
+    Ok(capacity.unwrap_or(1))
+}
+
+/// windows and units of the active reservations on the resource overlapping `[start, end)`,
+/// leaving out those in `exclude`
+async fn booked_spans(
+    conn: &mut PgConnection,
+    resource_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    exclude: &[Uuid],
+) -> Result<Vec<(DateTime<Utc>, DateTime<Utc>, i32)>, abi::Error> {
+    let spans = sqlx::query_as(
+        r#"
+        SELECT lower(timespan), upper(timespan), COALESCE(units, 1) FROM rsvp.reservations
+        WHERE resource_id = $1 AND timespan && $2 AND status <> 'cancelled'
+            AND NOT id = ANY($3)
+        "#,
+    )
+    .bind(resource_id)
+    .bind(PgRange::from(start..end))
+    .bind(exclude)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(spans)
+}
+
+/// the blackout and capacity checks `insert` runs for `units` of the resource during
+/// `[start, end)`, not counting the reservations in `exclude`. Returns whether the resource is
+/// shared (capacity above 1), in which case its lock is held until the transaction ends.
+async fn check_slot(
+    conn: &mut PgConnection,
+    resource_id: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    units: i32,
+    exclude: &[Uuid],
+) -> Result<bool, abi::Error> {
+    let blackout: Option<(String, DateTime<Utc>, DateTime<Utc>)> = sqlx::query_as(
+        r#"
+        SELECT resource_id, lower(timespan), upper(timespan) FROM rsvp.blackouts
+        WHERE resource_id = $1 AND timespan && $2 LIMIT 1
+        "#,
+    )
+    .bind(resource_id)
+    .bind(PgRange::from(start..end))
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if let Some((rid, start, end)) = blackout {
+        return Err(abi::Error::ResourceUnavailable {
+            window: ReservationWindow { rid, start, end },
+        });
+    }
+
+    let capacity = capacity_of(&mut *conn, resource_id).await?;
+    let exceeded = || abi::Error::CapacityExceeded {
+        resource_id: resource_id.to_string(),
+        capacity,
+    };
+    if units > capacity {
+        return Err(exceeded());
+    }
+    let shared = capacity > 1;
+    if shared {
+        // serialize reservations of the same resource so the count below can't race
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('resource:' || $1))")
+            .bind(resource_id)
+            .execute(&mut *conn)
+            .await?;
+
+        let spans = booked_spans(&mut *conn, resource_id, start, end, exclude).await?;
+        let used = split_by_edges(start, end, &spans)
+            .into_iter()
+            .map(|(_, _, used)| used)
+            .max()
+            .unwrap_or(0);
+        if used + units > capacity {
+            return Err(exceeded());
+        }
+    }
+
+    Ok(shared)
+}
+
+/// split `[start, end)` at every edge of `spans` inside it, summing the units of the spans covering each segment
+fn split_by_edges(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    spans: &[(DateTime<Utc>, DateTime<Utc>, i32)],
+) -> Vec<(DateTime<Utc>, DateTime<Utc>, i32)> {
+    let mut edges: Vec<DateTime<Utc>> = spans
+        .iter()
+        .flat_map(|(s, e, _)| [*s, *e])
+        .filter(|t| start < *t && *t < end)
+        .chain([start, end])
+        .collect();
+    edges.sort();
+    edges.dedup();
+
+    edges
+        .windows(2)
+        .map(|w| {
+            let used = spans
+                .iter()
+                .filter(|(s, e, _)| *s < w[1] && w[0] < *e)
+                .map(|(_, _, units)| units)
+                .sum();
+            (w[0], w[1], used)
+        })
+        .collect()
+}
+
+fn weekday_from_sunday(day: i16) -> Weekday {
+    // the table constrains weekday to 0..=6
+    (0..day).fold(Weekday::Sun, |d, _| d.succ())
+}
+
+fn weekday_from_monday(day: u32) -> Weekday {
+    (0..day).fold(Weekday::Mon, |d, _| d.succ())
+}
+
+/// the backend serving `conn`, needed to cancel its query. Only looked up when there is
+/// a token to honor.
+async fn backend_pid(
+    conn: &mut PgConnection,
+    cancel: Option<&CancellationToken>,
+) -> Result<Option<i32>, abi::Error> {
+    match cancel {
+        Some(_) => Ok(Some(
+            sqlx::query_scalar("SELECT pg_backend_pid()")
+                .fetch_one(conn)
+                .await?,
+        )),
+        None => Ok(None),
+    }
+}
+
+/// await `fut`, which runs on backend `pid`. If `cancel` fires first, the running query is
+/// cancelled server-side and `Error::Cancelled` returned.
+async fn cancellable<T, E>(
+    pool: &PgPool,
+    pid: Option<i32>,
+    cancel: Option<&CancellationToken>,
+    fut: impl Future<Output = Result<T, E>>,
+) -> Result<T, abi::Error>
+where
+    abi::Error: From<E>,
+{
+    let (Some(pid), Some(cancel)) = (pid, cancel) else {
+        return Ok(fut.await?);
+    };
+
+    tokio::pin!(fut);
+    tokio::select! {
+        biased;
+        _ = cancel.cancelled() => {
+            sqlx::query("SELECT pg_cancel_backend($1)")
+                .bind(pid)
+                .execute(pool)
+                .await?;
+            // let the cancelled query fail so its connection is clean when returned to the pool
+            let _ = fut.await;
+            Err(abi::Error::Cancelled)
+        }
+        ret = &mut fut => Ok(ret?),
+    }
+}
+
+fn str_to_option(s: &str) -> Option<&str> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
+
+impl ReservationManager {
+    pub fn new(pool: PgPool) -> ReservationManager {
+        Self {
+            pool,
+            forbid_user_double_booking: false,
+            auto_confirm: false,
+            dedupe_exact_retries: false,
+            strict_constraints: false,
+            max_active_per_user: None,
+            hours_quota: None,
+            clock: Arc::new(SystemClock),
+            lead_time: LeadTimePolicy::default(),
+            policies: vec![],
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            sink: Arc::new(NoopSink),
+            tenant_schemas: vec![],
+        }
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self.lead_time = self.lead_time.with_clock(self.clock.clone());
+        self
+    }
+
+    /// require reservations to start at least `lead_time` from now
+    pub fn with_min_lead_time(mut self, lead_time: Duration) -> Self {
+        self.lead_time = self.lead_time.with_min_lead_time(lead_time);
+        self
+    }
+
+    /// override the minimum lead time for a single resource
+    pub fn with_resource_min_lead_time(
+        mut self,
+        resource_id: impl Into<ResourceId>,
+        lead_time: Duration,
+    ) -> Self {
+        self.lead_time = self
+            .lead_time
+            .with_resource_min_lead_time(resource_id, lead_time);
+        self
+    }
+
+    /// add a policy to run, after the built-in lead time check and in registration order,
+    /// before every insert
+    pub fn with_policy(mut self, policy: impl ReservationPolicy + 'static) -> Self {
+        self.policies.push(Arc::new(policy));
+        self
+    }
+
+    /// mirror every new reservation to `sink`, see `ReservationSink`
+    pub fn with_sink(mut self, sink: impl ReservationSink + 'static) -> Self {
+        self.sink = Arc::new(sink);
+        self
+    }
+
+    /// commit reservations made by `insert`, releasing them externally if the commit fails
+    async fn commit_reserved(
+        &self,
+        tx: Transaction<'_, Postgres>,
+        rsvps: &[abi::Reservation],
+    ) -> Result<(), abi::Error> {
+        if let Err(e) = tx.commit().await {
+            self.release_external(rsvps).await;
+            return Err(e.into());
+        }
+        Ok(())
+    }
+
+    async fn release_external(&self, rsvps: &[abi::Reservation]) {
+        for rsvp in rsvps {
+            // best effort, the local reservation is gone either way
+            let _ = self.sink.release_external(rsvp).await;
+        }
+    }
+
+    fn check_policies(&self, rsvp: &abi::Reservation) -> Result<(), abi::Error> {
+        self.lead_time.check(rsvp)?;
+        self.policies
+            .iter()
+            .try_for_each(|policy| policy.check(rsvp))
+    }
+
+    /// insert new reservations as confirmed instead of pending
+    pub fn with_auto_confirm(mut self, enabled: bool) -> Self {
+        self.auto_confirm = enabled;
+        self
+    }
+
+    /// check the conflict constraint exists before every insert, refusing to reserve without it
+    pub fn with_strict_constraints(mut self, enabled: bool) -> Self {
+        self.strict_constraints = enabled;
+        self
+    }
+
+    /// audit these schemas' `reservations` tables against each other in `cross_tenant_overlaps`
+    pub fn with_tenant_schemas(
+        mut self,
+        schemas: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.tenant_schemas = schemas.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// treat a reserve for the exact same user, resource and window as an existing active
+    /// reservation as a retry and return that reservation instead of a conflict
+    pub fn with_exact_retry_dedupe(mut self, enabled: bool) -> Self {
+        self.dedupe_exact_retries = enabled;
+        self
+    }
+
+    /// limit how many pending or confirmed reservations a single user may hold
+    pub fn with_max_active_per_user(mut self, max: u32) -> Self {
+        self.max_active_per_user = Some(max);
+        self
+    }
+
+    /// limit the time a user may have booked during `[now, now + period)`. Only the parts of
+    /// pending or confirmed reservations falling into that window count.
+    pub fn with_hours_quota(mut self, max: Duration, period: Duration) -> Self {
+        self.hours_quota = Some((max, period));
+        self
+    }
+
+    /// reject reservations overlapping any existing reservation of the same user, on any resource
+    pub fn with_user_double_booking_check(mut self, enabled: bool) -> Self {
+        self.forbid_user_double_booking = enabled;
+        self
+    }
+
+    async fn insert(
+        &self,
+        tx: &mut Transaction<'_, Postgres>,
+        mut rsvp: abi::Reservation,
+    ) -> Result<abi::Reservation, abi::Error> {
+        rsvp.validate()?;
+
+        if self.strict_constraints {
+            verify_conflict_constraint(&mut *tx).await?;
+        }
+
+        if self.dedupe_exact_retries {
+            let existing = sqlx::query_as::<_, abi::Reservation>(
+                r#"
+                SELECT * FROM rsvp.reservations
+                WHERE user_id = $1 AND resource_id = $2 AND timespan = $3 AND status <> 'cancelled'
+                LIMIT 1
+                "#,
+            )
+            .bind(&rsvp.user_id)
+            .bind(&rsvp.resource_id)
+            .bind(rsvp.get_timespan())
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if let Some(existing) = existing {
+                return Ok(existing);
+            }
+        }
+
+        self.check_policies(&rsvp)?;
+
+        // an assigned approver has to confirm it, see `approve`
+        let approval = if rsvp.approver_id.is_empty() {
+            ApprovalStatus::NotRequired
+        } else {
+            ApprovalStatus::Pending
+        };
+        let status = if approval == ApprovalStatus::Pending {
+            ReservationStatus::Pending
+        } else if self.auto_confirm
+            && rsvp.status != ReservationStatus::Blocked as i32
+            // a hold stays pending until confirmed or expired, see `hold`
+            && rsvp.expires_at.is_none()
+        {
+            ReservationStatus::Confirmed
+        } else {
+            ReservationStatus::from_i32(rsvp.status).unwrap_or(ReservationStatus::Pending)
+        };
+
+        let range: PgRange<DateTime<Utc>> = rsvp.get_timespan();
+
+        let shared = check_slot(
+            &mut *tx,
+            &rsvp.resource_id,
+            abi::to_datetime(rsvp.start_time.as_ref())?,
+            abi::to_datetime(rsvp.end_time.as_ref())?,
+            rsvp.units.unwrap_or(1),
+            &[],
+        )
+        .await?;
+
+        if self.forbid_user_double_booking
+            || self.max_active_per_user.is_some()
+            || self.hours_quota.is_some()
+        {
+            // serialize reservations of the same user so the checks below can't race
+            sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+                .bind(&rsvp.user_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        if let Some(max) = self.max_active_per_user {
+            let active: i64 = sqlx::query_scalar(
+                r#"
+                SELECT count(*) FROM rsvp.reservations
+                WHERE user_id = $1 AND status IN ('pending', 'confirmed')
+                "#,
+            )
+            .bind(&rsvp.user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if active >= max.into() {
+                return Err(abi::Error::UserQuotaExceeded { max });
+            }
+        }
+
+        if let Some((max, period)) = self.hours_quota {
+            let now = self.clock.now();
+            let window = PgRange::from(now..now + period);
+            let booked: i64 = sqlx::query_scalar(
+                r#"
+                SELECT COALESCE(extract(epoch FROM sum(upper(timespan * $2) - lower(timespan * $2))), 0)::bigint
+                FROM rsvp.reservations
+                WHERE user_id = $1 AND timespan && $2 AND status IN ('pending', 'confirmed')
+                "#,
+            )
+            .bind(&rsvp.user_id)
+            .bind(window)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            let (start, end) = (
+                abi::to_datetime(rsvp.start_time.as_ref())?.max(now),
+                abi::to_datetime(rsvp.end_time.as_ref())?.min(now + period),
+            );
+            let added = (end - start).max(Duration::zero());
+            let would_be = Duration::seconds(booked) + added;
+            if added > Duration::zero() && would_be > max {
+                return Err(abi::Error::HoursQuotaExceeded { max, would_be });
+            }
+        }
+
+        if self.forbid_user_double_booking {
+            let conflicting: Option<Uuid> = sqlx::query_scalar(
+                r#"
+                SELECT id FROM rsvp.reservations
+                WHERE user_id = $1 AND timespan && $2 AND status <> 'cancelled' LIMIT 1
+                "#,
+            )
+            .bind(&rsvp.user_id)
+            .bind(range.clone())
+            .fetch_optional(&mut *tx)
+            .await?;
+
+            if let Some(id) = conflicting {
+                return Err(abi::Error::UserDoubleBooked {
+                    conflicting_id: id.to_string(),
+                });
+            }
+        }
+
+        let sql = r#"
+            INSERT INTO rsvp.reservations (
+                id, user_id, resource_id, timespan, note, status, created_by, shared, bundle_id,
+                expires_at, amount_cents, currency, party_size, special_requests, approver_id,
+                approval_status, units
+            )
+            VALUES (
+                COALESCE($1, gen_random_uuid()), $2, $3, $4, $5, $6::rsvp.reservation_status, $7, $8,
+                $9, $10, $11, $12, $13, $14, $15, $16, $17
+            )
+            RETURNING id, reference, version
+        "#;
+        // a non-empty id was pre-generated by the caller, see `reserve_with_id`
+        let id = match rsvp.id.as_str() {
+            "" => None,
+            id => Some(
+                Uuid::parse_str(id)
+                    .map_err(|_| abi::Error::InvalidReservationId(id.to_string()))?,
+            ),
+        };
+        let bundle_id = match rsvp.bundle_id.as_str() {
+            "" => None,
+            id => Some(
+                Uuid::parse_str(id)
+                    .map_err(|_| abi::Error::InvalidReservationId(id.to_string()))?,
+            ),
+        };
+        let expires_at = match rsvp.expires_at.as_ref() {
+            Some(_) => Some(abi::to_datetime(rsvp.expires_at.as_ref())?),
+            None => None,
+        };
+        let row = sqlx::query(sql)
+            .bind(id)
+            .bind(rsvp.user_id.clone())
+            .bind(rsvp.resource_id.clone())
+            .bind(range)
+            .bind(rsvp.note.clone())
+            .bind(status.to_string())
+            .bind(str_to_option(&rsvp.created_by))
+            .bind(shared)
+            .bind(bundle_id)
+            .bind(expires_at)
+            .bind(rsvp.amount_cents)
+            .bind(rsvp.currency.clone())
+            .bind(rsvp.party_size)
+            .bind(&rsvp.special_requests)
+            .bind(str_to_option(&rsvp.approver_id))
+            .bind(approval.to_string())
+            .bind(rsvp.units)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        rsvp.id = row.get::<Uuid, _>("id").to_string();
+        rsvp.reference = row.get("reference");
+        rsvp.version = row.get("version");
+        rsvp.status = status as i32;
+        rsvp.approval_status = approval as i32;
+        write_outbox(tx, OutboxEventKind::Reserved, &rsvp).await?;
+
+        if let Err(e) = self.sink.reserve_external(&rsvp).await {
+            // the external side may have recorded it before failing
+            self.release_external(std::slice::from_ref(&rsvp)).await;
+            return Err(e);
+        }
+        Ok(rsvp)
+    }
+
+    /// hard-delete cancelled reservations cancelled before `older_than`, returning how many were removed.
+    /// Only rows in the `cancelled` status are ever touched.
+    pub async fn purge_cancelled(&self, older_than: DateTime<Utc>) -> Result<u64, abi::Error> {
+        let ret = sqlx::query(
+            r#"
+            DELETE FROM rsvp.reservations
+            WHERE status = 'cancelled' AND cancelled_at IS NOT NULL AND cancelled_at < $1
+            "#,
+        )
+        .bind(older_than)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(ret.rows_affected())
+    }
+
+    fn bind_cursor_filter<'q, O>(
+        &self,
+        q: QueryAs<'q, Postgres, O, PgArguments>,
+        query: &'q abi::ReservationQuery,
+        cursor: CursorKey,
+    ) -> QueryAs<'q, Postgres, O, PgArguments> {
+        bind_filter(q, query)
+            .bind(cursor.map(|(start, _)| start))
+            .bind(cursor.map(|(_, id)| id))
+    }
+
+    async fn cursor_exists(
+        &self,
+        query: &abi::ReservationQuery,
+        op: &str,
+        cursor: CursorKey,
+    ) -> Result<bool, abi::Error> {
+        let sql = format!(
+            "SELECT EXISTS (SELECT 1 FROM rsvp.reservations WHERE {} AND ($12::timestamptz IS NULL OR (lower(timespan), id) {} ($12, $13::uuid)))",
+            QUERY_FILTER, op
+        );
+        let (exists,): (bool,) = self
+            .bind_cursor_filter(sqlx::query_as(&sql), query, cursor)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(exists)
+    }
+
+    /// every pair of active reservations, not both shared, overlapping on the same resource.
+    /// Should be empty while the exclusion constraint is in place.
+    pub async fn find_all_overlaps(
+        &self,
+    ) -> Result<Vec<(abi::Reservation, abi::Reservation)>, abi::Error> {
+        let mut conn = self.pool.acquire().await?;
+        find_overlaps(&mut conn).await
+    }
+
+    /// load reservations with `COPY` into a staging table, then move them into
+    /// `rsvp.reservations` in one statement.
+    ///
+    /// Overlaps and capacity overruns don't abort the import, every row is imported and they are
+    /// reported instead, in input order. The conflict constraint is only dropped when an overlap
+    /// needs it to be, see `ImportReport::constraint_dropped`.
+    pub async fn bulk_import(
+        &self,
+        rsvps: impl Stream<Item = abi::Reservation>,
+    ) -> Result<ImportReport, abi::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"
+            CREATE TEMP TABLE import_staging (LIKE rsvp.reservations INCLUDING DEFAULTS, seq bigserial)
+            ON COMMIT DROP
+            "#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        let mut copy = tx
+            .copy_in_raw(&format!(
+                "COPY import_staging ({IMPORT_COLUMNS}) FROM STDIN WITH (FORMAT csv)"
+            ))
+            .await?;
+        let mut buf = String::new();
+        futures::pin_mut!(rsvps);
+        while let Some(rsvp) = rsvps.next().await {
+            match copy_row(&rsvp) {
+                Ok(row) => buf.push_str(&row),
+                Err(e) => {
+                    copy.abort("invalid reservation").await?;
+                    return Err(e);
+                }
+            }
+            if buf.len() >= COPY_CHUNK_SIZE {
+                copy.send(std::mem::take(&mut buf).into_bytes()).await?;
+            }
+        }
+        if !buf.is_empty() {
+            copy.send(buf.into_bytes()).await?;
+        }
+        copy.finish().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE import_staging s
+            SET shared = COALESCE((SELECT capacity FROM rsvp.resources r WHERE r.id = s.resource_id), 1) > 1
+            "#,
+        )
+        .execute(&mut tx)
+        .await?;
+
+        // the same predicate as the conflict constraint. The imported row comes first, paired with
+        // later imported rows and with stored ones
+        let pairs: Vec<(Uuid, Uuid)> = sqlx::query_as(
+            r#"
+            SELECT a.id, b.id FROM import_staging a
+            JOIN (
+                SELECT id, resource_id, timespan, status, shared, seq FROM import_staging
+                UNION ALL
+                SELECT id, resource_id, timespan, status, shared, NULL FROM rsvp.reservations
+            ) b
+            ON a.resource_id = b.resource_id AND a.timespan && b.timespan
+                AND (a.seq < b.seq OR b.seq IS NULL)
+            WHERE a.status <> 'cancelled' AND b.status <> 'cancelled' AND NOT (a.shared AND b.shared)
+            ORDER BY a.seq, b.seq NULLS LAST, lower(b.timespan), b.id
+            "#,
+        )
+        .fetch_all(&mut tx)
+        .await?;
+
+        let constraint_dropped = !pairs.is_empty();
+        if constraint_dropped {
+            sqlx::query(
+                "ALTER TABLE rsvp.reservations DROP CONSTRAINT IF EXISTS reservations_conflict",
+            )
+            .execute(&mut tx)
+            .await?;
+        }
+        let imported = sqlx::query(&format!(
+            r#"
+            INSERT INTO rsvp.reservations ({IMPORT_COLUMNS}, shared)
+            SELECT {IMPORT_COLUMNS}, shared FROM import_staging
+            "#
+        ))
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        let overlaps = load_pairs(&mut tx, "rsvp.reservations", pairs).await?;
+
+        // usage only steps up where a reservation starts, so checking every start inside an
+        // imported row's window finds any overrun during it
+        let over_capacity = sqlx::query_as::<_, abi::Reservation>(
+            r#"
+            SELECT r.* FROM rsvp.reservations r
+            JOIN import_staging s USING (id)
+            JOIN rsvp.resources c ON c.id = r.resource_id
+            WHERE r.shared AND r.status <> 'cancelled' AND EXISTS (
+                SELECT 1 FROM rsvp.reservations p
+                WHERE p.resource_id = r.resource_id AND p.status <> 'cancelled'
+                    AND p.timespan && r.timespan
+                    AND (
+                        SELECT sum(COALESCE(u.units, 1)) FROM rsvp.reservations u
+                        WHERE u.resource_id = r.resource_id AND u.status <> 'cancelled'
+                            AND u.timespan @> greatest(lower(p.timespan), lower(r.timespan))
+                    ) > c.capacity
+            )
+            ORDER BY s.seq
+            "#,
+        )
+        .fetch_all(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(ImportReport {
+            imported,
+            overlaps,
+            over_capacity,
+            constraint_dropped,
+        })
+    }
+
+    /// make sure the database detects conflicting reservations, e.g. at startup.
+    /// Fails with `MissingConstraint` if the exclusion constraint is absent.
+    pub async fn verify_constraints(&self) -> Result<(), abi::Error> {
+        let mut conn = self.pool.acquire().await?;
+        verify_conflict_constraint(&mut conn).await
+    }
+
+    /// re-add the conflict constraint if it went missing, e.g. dropped by `bulk_import`.
+    /// Fails while overlaps exist.
+    pub async fn restore_conflict_constraint(&self) -> Result<(), abi::Error> {
+        sqlx::query(ADD_CONFLICT_CONSTRAINT)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    fn emit(&self, kind: ReservationEventKind, rsvp: &abi::Reservation) {
+        // no subscribers is not an error
+        let _ = self.events.send(ReservationEvent {
+            kind,
+            reservation: rsvp.clone(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use abi::{
+        Reservation, ReservationConflict, ReservationConflictInfo, ReservationQueryBuilder,
+        ReservationWindow,
+    };
+    use chrono::{DateTime, FixedOffset};
+
+    use crate::new_reservation_id;
+
+    use super::*;
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_should_work_for_valid_window() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let start: DateTime<FixedOffset> = "2022-12-25T15:00:00-0700".parse().unwrap();
+        let end: DateTime<FixedOffset> = "2022-12-28T12:00:00-0700".parse().unwrap();
+        let rsvp = Reservation::new_pending("tyrid", "1121", start, end, "......");
+        let res = manager.reserve(rsvp).await;
+        assert!(res.is_ok());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_conflict_reservation_should_reject() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let rsvp1 = Reservation::new_pending(
+            "tyrid",
+            "1121",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "hello",
+        );
+        let rsvp2 = Reservation::new_pending(
+            "aliceid",
+            "1121",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "world",
+        );
+
+        let _rsvp1 = manager.reserve(rsvp1).await.unwrap();
+        let err = manager.reserve(rsvp2).await.unwrap_err();
+
+        let info = ReservationConflictInfo::Parsed(ReservationConflict {
+            new: ReservationWindow {
+                rid: "1121".to_string(),
+                start: "2022-12-26T15:00:00-0700".parse().unwrap(),
+                end: "2022-12-30T12:00:00-0700".parse().unwrap(),
+            },
+            old: ReservationWindow {
+                rid: "1121".to_string(),
+                start: "2022-12-25T15:00:00-0700".parse().unwrap(),
+                end: "2022-12-28T12:00:00-0700".parse().unwrap(),
+            },
+        });
+
+        assert_eq!(err, abi::Error::ConflictReservation(info));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_batch_best_effort_should_report_each_item() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let rsvp1 = Reservation::new_pending(
+            "tyrid",
+            "1121",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "hello",
+        );
+        let rsvp2 = Reservation::new_pending(
+            "aliceid",
+            "1121",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "world",
+        );
+
+        let results = manager
+            .reserve_batch_best_effort(vec![rsvp1, rsvp2])
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        let rsvp1 = results[0].as_ref().unwrap();
+        assert!(!rsvp1.id.is_empty());
+        assert!(matches!(
+            results[1],
+            Err(abi::Error::ConflictReservation(_))
+        ));
+
+        let rsvp = manager.get(rsvp1.id.clone()).await.unwrap();
+        assert_eq!(rsvp.user_id, "tyrid");
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_change_status_should_work() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let rsvp = Reservation::new_pending(
+            "aliceId",
+            "1021",
+            "2023-01-25T15:00:00-0700".parse().unwrap(),
+            "2023-02-25T12:00:00-0700".parse().unwrap(),
+            "hello...",
+        );
+        let rsvp = manager.reserve(rsvp).await.unwrap();
+
+        let res = manager.change_status(rsvp.id, "").await.unwrap();
+
+        assert_eq!(res.status, abi::ReservationStatus::Confirmed as i32);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_change_status_not_pending_should_do_nothings() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let rsvp = Reservation::new_pending(
+            "aliceId",
+            "1021",
+            "2023-01-25T15:00:00-0700".parse().unwrap(),
+            "2023-02-25T12:00:00-0700".parse().unwrap(),
+            "hello...",
+        );
+        let rsvp = manager.reserve(rsvp).await.unwrap();
+
+        let rsvp = manager.change_status(rsvp.id, "").await.unwrap();
+
+        assert_eq!(rsvp.status, abi::ReservationStatus::Confirmed as i32);
+
+        let ret = manager.change_status(rsvp.id, "").await.unwrap_err();
+
+        assert_eq!(ret, abi::Error::NotFound);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn update_note_should_work() {
+        let (manager, rsvp) = make_alice_reservation(&migrated_pool.clone()).await;
+
+        let rsvp = manager
+            .update_note(rsvp.id, "world.".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(rsvp.note, "world.");
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn get_reservation_should_work() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool.clone()).await;
+
+        let res = manager.get(rsvp.id).await.unwrap();
+
+        assert_eq!(res.user_id, rsvp.user_id);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn dalete_reservation_should_work() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool.clone()).await;
+
+        manager.delete(rsvp.id.clone()).await.unwrap();
+        let err = manager.get(rsvp.id).await.unwrap_err();
+        println!("{:?}", err);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_reservation_should_work() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool.clone()).await;
+
+        let query = ReservationQueryBuilder::default()
+            .user_id("tyrId")
+            .resource_id("1021")
+            .start(
+                "2022-12-25T15:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-28T12:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .build()
+            .unwrap();
+
+        let rsvps = manager.query(query).await.unwrap();
+
+        assert_eq!(rsvps.len(), 1);
+        assert!(rsvps[0].same_booking(&rsvp));
+
+        let query = ReservationQueryBuilder::default()
+            .user_id("tyrId")
+            .resource_id("1021")
+            .start(
+                "2023-01-25T15:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2023-02-28T12:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .build()
+            .unwrap();
+
+        let rsvps1 = manager.query(query).await.unwrap();
+
+        assert!(rsvps1.is_empty());
+
+        let _rsvp = manager
+            .change_status(rsvps[0].id.clone(), "")
+            .await
+            .unwrap();
+
+        let query = ReservationQueryBuilder::default()
+            .user_id("tyrId")
+            .resource_id("1021")
+            .start(
+                "2022-12-25T15:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-28T12:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .build()
+            .unwrap();
+
+        let rsvps1 = manager.query(query).await.unwrap();
+
+        assert!(rsvps1.is_empty());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn neighbors_should_find_reservations_on_both_sides() {
+        let (manager, before) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "1021",
+            "2022-12-20T15:00:00-0700",
+            "2022-12-22T12:00:00-0700",
+            "before",
+        )
+        .await;
+        let (_, middle) = make_tyr_reservation(&migrated_pool).await;
+        let (_, after) = make_alice_reservation(&migrated_pool).await;
+        make_reservation(
+            &migrated_pool,
+            "bobId",
+            "1022",
+            "2022-12-23T15:00:00-0700",
+            "2022-12-24T12:00:00-0700",
+            "other resource",
+        )
+        .await;
+
+        let (prev, next) = manager.neighbors(middle.id).await.unwrap();
+
+        assert_eq!(prev.unwrap().id, before.id);
+        assert_eq!(next.unwrap().id, after.id);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn neighbors_should_skip_cancelled_reservations() {
+        let (manager, before) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "1021",
+            "2022-12-20T15:00:00-0700",
+            "2022-12-22T12:00:00-0700",
+            "before",
+        )
+        .await;
+        let (_, middle) = make_tyr_reservation(&migrated_pool).await;
+        let (_, after) = make_alice_reservation(&migrated_pool).await;
+        for (start, end) in [
+            ("2022-12-23T15:00:00-0700", "2022-12-24T12:00:00-0700"),
+            ("2023-01-01T15:00:00-0700", "2023-01-02T12:00:00-0700"),
+        ] {
+            let (_, rsvp) =
+                make_reservation(&migrated_pool, "bobId", "1021", start, end, "cancelled").await;
+            manager.cancel(rsvp.id).await.unwrap();
+        }
+
+        let (prev, next) = manager.neighbors(middle.id).await.unwrap();
+
+        assert_eq!(prev.unwrap().id, before.id);
+        assert_eq!(next.unwrap().id, after.id);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_naive_times_in_tz_should_store_utc() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let tz = FixedOffset::west(7 * 3600);
+        let rsvp = Reservation::new_pending_in_tz(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00".parse().unwrap(),
+            "2022-12-28T12:00:00".parse().unwrap(),
+            tz,
+            "naive",
+        );
+        let rsvp = manager.reserve(rsvp).await.unwrap();
+
+        let rsvp = manager.get(rsvp.id).await.unwrap();
+        let start = abi::to_datetime(rsvp.start_time.as_ref()).unwrap();
+        let end = abi::to_datetime(rsvp.end_time.as_ref()).unwrap();
+        assert_eq!(start.to_rfc3339(), "2022-12-25T22:00:00+00:00");
+        assert_eq!(end.to_rfc3339(), "2022-12-28T19:00:00+00:00");
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn occupancy_snapshot_should_return_active_confirmed_reservations() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let at: DateTime<FixedOffset> = "2022-12-26T12:00:00-0700".parse().unwrap();
+        for (uid, rid, start, end) in [
+            (
+                "tyrId",
+                "1021",
+                "2022-12-25T15:00:00-0700",
+                "2022-12-28T12:00:00-0700",
+            ),
+            (
+                "aliceId",
+                "1022",
+                "2022-12-26T09:00:00-0700",
+                "2022-12-26T18:00:00-0700",
+            ),
+            (
+                "bobId",
+                "1023",
+                "2022-12-27T09:00:00-0700",
+                "2022-12-27T18:00:00-0700",
+            ),
+        ] {
+            let rsvp = Reservation::new_pending(
+                uid,
+                rid,
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            );
+            let rsvp = manager.reserve(rsvp).await.unwrap();
+            manager.change_status(rsvp.id, "").await.unwrap();
+        }
+
+        let snapshot = manager
+            .occupancy_snapshot(Some(at.with_timezone(&Utc)))
+            .await
+            .unwrap();
+
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].0, "1021");
+        assert_eq!(snapshot[0].1.user_id, "tyrId");
+        assert_eq!(snapshot[1].0, "1022");
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_user_double_booking_should_reject_when_enabled() {
+        let manager =
+            ReservationManager::new(migrated_pool.clone()).with_user_double_booking_check(true);
+        let rsvp1 = Reservation::new_pending(
+            "tyrId",
+            "room-1",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let rsvp2 = Reservation::new_pending(
+            "tyrId",
+            "room-2",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-30T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let rsvp1 = manager.reserve(rsvp1).await.unwrap();
+
+        let err = manager.reserve(rsvp2.clone()).await.unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::UserDoubleBooked {
+                conflicting_id: rsvp1.id
+            }
+        );
+
+        let manager = ReservationManager::new(migrated_pool.clone());
+        assert!(manager.reserve(rsvp2).await.is_ok());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_user_double_booking_should_ignore_cancelled() {
+        let manager =
+            ReservationManager::new(migrated_pool.clone()).with_user_double_booking_check(true);
+        let rsvp = |rid: &str| {
+            Reservation::new_pending(
+                "tyrId",
+                rid,
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+        let first = manager.reserve(rsvp("room-1")).await.unwrap();
+        manager.cancel(first.id).await.unwrap();
+
+        assert!(manager.reserve(rsvp("room-2")).await.is_ok());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn cancel_reservation_should_free_the_window() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool).await;
+
+        let rsvp = manager.cancel(rsvp.id).await.unwrap();
+        assert_eq!(rsvp.status, abi::ReservationStatus::Cancelled as i32);
+
+        let (_, rsvp) = make_tyr_reservation(&migrated_pool).await;
+        assert_eq!(rsvp.status, abi::ReservationStatus::Pending as i32);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn purge_cancelled_should_only_remove_old_cancelled_reservations() {
+        let (manager, old) = make_tyr_reservation(&migrated_pool).await;
+        let (_, recent) = make_alice_reservation(&migrated_pool).await;
+        let (_, active) = make_reservation(
+            &migrated_pool,
+            "bobId",
+            "1022",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-28T12:00:00-0700",
+            "",
+        )
+        .await;
+        manager.cancel(old.id.clone()).await.unwrap();
+        manager.cancel(recent.id.clone()).await.unwrap();
+        sqlx::query(
+            "UPDATE rsvp.reservations SET cancelled_at = now() - interval '30 days' WHERE id = $1",
+        )
+        .bind(Uuid::parse_str(&old.id).unwrap())
+        .execute(&migrated_pool)
+        .await
+        .unwrap();
+
+        let cutoff = Utc::now() - chrono::Duration::days(7);
+        let purged = manager.purge_cancelled(cutoff).await.unwrap();
+
+        assert_eq!(purged, 1);
+        assert_eq!(manager.get(old.id).await.unwrap_err(), abi::Error::NotFound);
+        assert!(manager.get(recent.id).await.is_ok());
+        assert!(manager.get(active.id).await.is_ok());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_search_should_match_user_resource_and_note() {
+        let (manager, by_user) = make_reservation(
+            &migrated_pool,
+            "vip-guest",
+            "1021",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-26T12:00:00-0700",
+            "",
+        )
+        .await;
+        let (_, by_resource) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "vip-room",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-26T12:00:00-0700",
+            "",
+        )
+        .await;
+        let (_, by_note) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "1022",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-26T12:00:00-0700",
+            "near the VIP lounge",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "bobId",
+            "1023",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-26T12:00:00-0700",
+            "v_p",
+        )
+        .await;
+
+        let query = ReservationQueryBuilder::default()
+            .search("vip")
+            .start(
+                "2022-12-25T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-27T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .build()
+            .unwrap();
+        let rsvps = manager.query(query).await.unwrap();
+
+        let mut ids: Vec<_> = rsvps.into_iter().map(|r| r.id).collect();
+        ids.sort();
+        let mut expected = vec![by_user.id, by_resource.id, by_note.id];
+        expected.sort();
+        assert_eq!(ids, expected);
+
+        // LIKE wildcards in the term are matched literally
+        let query = ReservationQueryBuilder::default()
+            .search("v_p")
+            .start(
+                "2022-12-25T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-27T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .build()
+            .unwrap();
+        let rsvps = manager.query(query).await.unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].user_id, "bobId");
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_by_cursor_should_page_forward_and_backward() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let mut ids = vec![];
+        for day in 20..25 {
+            let rsvp = Reservation::new_pending(
+                "tyrId",
+                "1021",
+                format!("2022-12-{}T10:00:00-0700", day).parse().unwrap(),
+                format!("2022-12-{}T12:00:00-0700", day).parse().unwrap(),
+                "",
+            );
+            ids.push(manager.reserve(rsvp).await.unwrap().id);
+        }
+        let query = ReservationQueryBuilder::default()
+            .user_id("tyrId")
+            .start(
+                "2022-12-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2023-01-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .pagesize(2)
+            .build()
+            .unwrap();
+        let page_ids =
+            |page: &CursorPage| page.rsvps.iter().map(|r| r.id.clone()).collect::<Vec<_>>();
+
+        let page1 = manager
+            .query_by_cursor(query.clone(), None, PageDirection::After)
+            .await
+            .unwrap();
+        assert_eq!(page_ids(&page1), ids[0..2]);
+        assert_eq!(page1.prev, None);
+
+        let page2 = manager
+            .query_by_cursor(query.clone(), page1.next.clone(), PageDirection::After)
+            .await
+            .unwrap();
+        assert_eq!(page_ids(&page2), ids[2..4]);
+        assert_eq!(page2.prev, Some(ids[2].clone()));
+        assert_eq!(page2.next, Some(ids[3].clone()));
+
+        let back = manager
+            .query_by_cursor(query.clone(), page2.prev.clone(), PageDirection::Before)
+            .await
+            .unwrap();
+        assert_eq!(page_ids(&back), page_ids(&page1));
+        assert_eq!(back.prev, None);
+        assert_eq!(back.next, Some(ids[1].clone()));
+
+        let last = manager
+            .query_by_cursor(query, page2.next, PageDirection::After)
+            .await
+            .unwrap();
+        assert_eq!(page_ids(&last), ids[4..]);
+        assert_eq!(last.next, None);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_by_cursor_should_apply_every_filter() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        for day in 20..24 {
+            let rsvp = Reservation {
+                party_size: Some(if day % 2 == 0 { 6 } else { 2 }),
+                created_by: if day == 20 {
+                    "agentId".into()
+                } else {
+                    "".into()
+                },
+                ..Reservation::new_pending(
+                    "tyrId",
+                    "1021",
+                    format!("2022-12-{}T10:00:00-0700", day).parse().unwrap(),
+                    format!("2022-12-{}T12:00:00-0700", day).parse().unwrap(),
+                    "",
+                )
+            };
+            manager.reserve(rsvp).await.unwrap();
+        }
+        let query = ReservationQueryBuilder::default()
+            .start(
+                "2022-12-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2023-01-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .min_party_size(4)
+            .build()
+            .unwrap();
+        let ids = |rsvps: Vec<Reservation>| rsvps.into_iter().map(|r| r.id).collect::<Vec<_>>();
+
+        let page = manager
+            .query_by_cursor(query.clone(), None, PageDirection::After)
+            .await
+            .unwrap();
+        assert_eq!(page.rsvps.len(), 2);
+        assert_eq!(
+            ids(page.rsvps),
+            ids(manager.query(query.clone()).await.unwrap())
+        );
+
+        let query = abi::ReservationQuery {
+            created_by: "agentId".into(),
+            ..query
+        };
+        let page = manager
+            .query_by_cursor(query.clone(), None, PageDirection::After)
+            .await
+            .unwrap();
+        assert_eq!(page.rsvps.len(), 1);
+        assert_eq!(ids(page.rsvps), ids(manager.query(query).await.unwrap()));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_with_auto_confirm_should_store_confirmed() {
+        let manager = ReservationManager::new(migrated_pool.clone()).with_auto_confirm(true);
+        let rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let rsvp = manager.reserve(rsvp).await.unwrap();
+        assert_eq!(rsvp.status, ReservationStatus::Confirmed as i32);
+
+        let rsvp = manager.get(rsvp.id).await.unwrap();
+        assert_eq!(rsvp.status, ReservationStatus::Confirmed as i32);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_by_agent_and_guest_should_combine_filters() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let mut ids = vec![];
+        for (i, (guest, agent)) in [
+            ("guest-x", "agent-a"),
+            ("guest-x", "agent-b"),
+            ("guest-y", "agent-a"),
+            ("guest-x", ""),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut rsvp = Reservation::new_pending(
+                guest,
+                format!("room-{}", i),
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "",
+            );
+            rsvp.created_by = agent.to_string();
+            ids.push(manager.reserve(rsvp).await.unwrap().id);
+        }
+        let query = |guest: &str, agent: &str| {
+            ReservationQueryBuilder::default()
+                .user_id(guest)
+                .created_by(agent)
+                .start(
+                    "2022-12-01T00:00:00-0700"
+                        .parse::<prost_types::Timestamp>()
+                        .unwrap(),
+                )
+                .end(
+                    "2023-01-01T00:00:00-0700"
+                        .parse::<prost_types::Timestamp>()
+                        .unwrap(),
+                )
+                .status(ReservationStatus::Pending)
+                .build()
+                .unwrap()
+        };
+
+        let rsvps = manager.query(query("guest-x", "agent-a")).await.unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].id, ids[0]);
+        assert_eq!(rsvps[0].created_by, "agent-a");
+
+        let rsvps = manager.query(query("", "agent-a")).await.unwrap();
+        assert_eq!(rsvps.len(), 2);
+
+        let rsvps = manager.query(query("guest-x", "")).await.unwrap();
+        assert_eq!(rsvps.len(), 3);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn try_reserve_should_report_each_outcome() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+
+        let outcome = manager.try_reserve(rsvp.clone()).await.unwrap();
+        assert!(matches!(outcome, ReserveOutcome::Created(ref r) if !r.id.is_empty()));
+
+        let outcome = manager.try_reserve(rsvp.clone()).await.unwrap();
+        assert!(matches!(
+            outcome,
+            ReserveOutcome::Conflict(ReservationConflictInfo::Parsed(_))
+        ));
+
+        let mut invalid = rsvp;
+        invalid.user_id = "".to_string();
+        let outcome = manager.try_reserve(invalid).await.unwrap();
+        assert_eq!(
+            outcome,
+            ReserveOutcome::Invalid(abi::ValidationError::InvalidUserId("".to_string()))
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn try_reserve_should_return_err_on_db_failure() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        sqlx::query("DROP TABLE rsvp.reservations CASCADE")
+            .execute(&migrated_pool)
+            .await
+            .unwrap();
+        let rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+
+        let err = manager.try_reserve(rsvp).await.unwrap_err();
+        assert!(matches!(err, abi::Error::DbError(_)));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_with_pad_should_include_nearby_reservations() {
+        let (manager, rsvp) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "1021",
+            "2022-12-25T09:50:00-0700",
+            "2022-12-25T11:00:00-0700",
+            "",
+        )
+        .await;
+        let query = |pad: Option<prost_types::Duration>| {
+            let mut builder = ReservationQueryBuilder::default();
+            builder
+                .resource_id("1021")
+                .start(
+                    "2022-12-25T10:00:00-0700"
+                        .parse::<prost_types::Timestamp>()
+                        .unwrap(),
+                )
+                .end(
+                    "2022-12-25T12:00:00-0700"
+                        .parse::<prost_types::Timestamp>()
+                        .unwrap(),
+                )
+                .status(ReservationStatus::Pending);
+            if let Some(pad) = pad {
+                builder.pad(pad);
+            }
+            builder.build().unwrap()
+        };
+
+        let rsvps = manager.query(query(None)).await.unwrap();
+        assert!(rsvps.is_empty());
+
+        let pad = prost_types::Duration {
+            seconds: 15 * 60,
+            nanos: 0,
+        };
+        let rsvps = manager.query(query(Some(pad))).await.unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].id, rsvp.id);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn next_available_per_resource_should_find_first_fitting_gap() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        for (rid, start, end) in [
+            (
+                "room-1",
+                "2022-12-25T09:00:00-0700",
+                "2022-12-25T10:00:00-0700",
+            ),
+            (
+                "room-1",
+                "2022-12-25T10:30:00-0700",
+                "2022-12-25T12:00:00-0700",
+            ),
+            (
+                "room-1",
+                "2022-12-25T13:00:00-0700",
+                "2022-12-25T14:00:00-0700",
+            ),
+        ] {
+            let rsvp = Reservation::new_pending(
+                "tyrId",
+                rid,
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            );
+            manager.reserve(rsvp).await.unwrap();
+        }
+        let from: DateTime<FixedOffset> = "2022-12-25T09:30:00-0700".parse().unwrap();
+        let from = from.with_timezone(&Utc);
+
+        let available = manager
+            .next_available_per_resource(
+                vec!["room-1".to_string(), "room-2".to_string()],
+                from,
+                Duration::hours(1),
+            )
+            .await
+            .unwrap();
+
+        let expected: DateTime<FixedOffset> = "2022-12-25T12:00:00-0700".parse().unwrap();
+        assert_eq!(available["room-1"], Some(expected.with_timezone(&Utc)));
+        assert_eq!(available["room-2"], Some(from));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_should_reject_window_crossing_blackout() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let start: DateTime<FixedOffset> = "2022-12-24T00:00:00-0700".parse().unwrap();
+        let end: DateTime<FixedOffset> = "2022-12-26T00:00:00-0700".parse().unwrap();
+        manager
+            .add_blackout(
+                "1021".to_string(),
+                start.with_timezone(&Utc),
+                end.with_timezone(&Utc),
+            )
+            .await
+            .unwrap();
+
+        let rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let err = manager.reserve(rsvp).await.unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::ResourceUnavailable {
+                window: ReservationWindow {
+                    rid: "1021".to_string(),
+                    start: start.with_timezone(&Utc),
+                    end: end.with_timezone(&Utc),
+                }
+            }
+        );
+
+        let rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-26T00:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        assert!(manager.reserve(rsvp).await.is_ok());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_etag_should_change_only_when_rows_change() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool).await;
+        let query = ReservationQueryBuilder::default()
+            .user_id("tyrId")
+            .start(
+                "2022-12-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2023-01-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .build()
+            .unwrap();
+
+        let etag1 = manager.query_etag(query.clone()).await.unwrap();
+        make_alice_reservation(&migrated_pool).await;
+        let etag2 = manager.query_etag(query.clone()).await.unwrap();
+        assert_eq!(etag1, etag2);
+
+        manager
+            .update_note(rsvp.id, "changed".to_string())
+            .await
+            .unwrap();
+        let etag3 = manager.query_etag(query).await.unwrap();
+        assert_ne!(etag1, etag3);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn swap_should_exchange_slots() {
+        let (manager, a) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "room-1",
+            "2022-12-25T10:00:00-0700",
+            "2022-12-25T12:00:00-0700",
+            "",
+        )
+        .await;
+        let (_, b) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "room-1",
+            "2022-12-25T12:00:00-0700",
+            "2022-12-25T15:00:00-0700",
+            "",
+        )
+        .await;
+
+        let (new_a, new_b) = manager.swap(a.id.clone(), b.id.clone()).await.unwrap();
+
+        assert_eq!(new_a.user_id, "tyrId");
+        assert_eq!(new_a.start_time, b.start_time);
+        assert_eq!(new_a.end_time, b.end_time);
+        assert_eq!(new_b.user_id, "aliceId");
+        assert_eq!(new_b.start_time, a.start_time);
+        assert_eq!(new_b.end_time, a.end_time);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn swap_colliding_with_third_reservation_should_roll_back() {
+        let (manager, a) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "room-1",
+            "2022-12-25T10:00:00-0700",
+            "2022-12-25T12:00:00-0700",
+            "",
+        )
+        .await;
+        let (_, b) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "room-2",
+            "2022-12-26T10:00:00-0700",
+            "2022-12-26T18:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "bobId",
+            "room-1",
+            "2022-12-26T12:00:00-0700",
+            "2022-12-26T13:00:00-0700",
+            "",
+        )
+        .await;
+
+        let err = manager.swap(a.id.clone(), b.id.clone()).await.unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+
+        let a2 = manager.get(a.id).await.unwrap();
+        let b2 = manager.get(b.id).await.unwrap();
+        assert_eq!(a2.resource_id, "room-1");
+        assert_eq!(a2.start_time, a.start_time);
+        assert_eq!(b2.resource_id, "room-2");
+        assert_eq!(b2.start_time, b.start_time);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn swap_should_check_blackouts_and_capacity() {
+        let (manager, a) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "room-1",
+            "2022-12-25T10:00:00-0700",
+            "2022-12-25T12:00:00-0700",
+            "",
+        )
+        .await;
+        let (_, b) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "room-2",
+            "2022-12-26T10:00:00-0700",
+            "2022-12-26T12:00:00-0700",
+            "",
+        )
+        .await;
+        manager
+            .add_blackout(
+                "room-1".into(),
+                "2022-12-26T00:00:00-0700".parse().unwrap(),
+                "2022-12-27T00:00:00-0700".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let err = manager.swap(a.id.clone(), b.id.clone()).await.unwrap_err();
+        assert!(matches!(err, abi::Error::ResourceUnavailable { .. }));
+        assert_eq!(manager.get(a.id).await.unwrap().start_time, a.start_time);
+
+        // a shared resource already full during b's slot
+        manager.set_capacity("bus".into(), 2).await.unwrap();
+        let mut on_bus = vec![];
+        for (uid, start, end) in [
+            (
+                "bobId",
+                "2022-12-26T10:00:00-0700",
+                "2022-12-26T12:00:00-0700",
+            ),
+            (
+                "carolId",
+                "2022-12-26T10:00:00-0700",
+                "2022-12-26T12:00:00-0700",
+            ),
+            (
+                "tyrId",
+                "2022-12-25T10:00:00-0700",
+                "2022-12-25T12:00:00-0700",
+            ),
+        ] {
+            let rsvp = Reservation::new_pending(
+                uid,
+                "bus",
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            );
+            on_bus.push(manager.reserve(rsvp).await.unwrap());
+        }
+
+        let err = manager
+            .swap(on_bus[2].id.clone(), b.id.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::CapacityExceeded {
+                resource_id: "bus".into(),
+                capacity: 2,
+            }
+        );
+        assert_eq!(manager.get(b.id).await.unwrap().start_time, b.start_time);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_note_regex_should_match_subset() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        for (i, note) in ["ref BK-1234", "ref bk-99", "no reference", "BK-7 and more"]
+            .into_iter()
+            .enumerate()
+        {
+            let rsvp = Reservation::new_pending(
+                "tyrId",
+                format!("room-{}", i),
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                note,
+            );
+            manager.reserve(rsvp).await.unwrap();
+        }
+        let query = |pattern: &str| {
+            ReservationQueryBuilder::default()
+                .note_regex(pattern)
+                .start(
+                    "2022-12-01T00:00:00-0700"
+                        .parse::<prost_types::Timestamp>()
+                        .unwrap(),
+                )
+                .end(
+                    "2023-01-01T00:00:00-0700"
+                        .parse::<prost_types::Timestamp>()
+                        .unwrap(),
+                )
+                .status(ReservationStatus::Pending)
+                .build()
+                .unwrap()
+        };
+
+        let rsvps = manager.query(query("BK-[0-9]+")).await.unwrap();
+        let mut notes: Vec<_> = rsvps.into_iter().map(|r| r.note).collect();
+        notes.sort();
+        assert_eq!(notes, vec!["BK-7 and more", "ref BK-1234"]);
+
+        let err = manager.query(query("BK-(")).await.unwrap_err();
+        assert!(matches!(err, abi::Error::InvalidQuery(_)));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_with_pre_generated_id_should_keep_it() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let id = new_reservation_id();
+        let rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+
+        let rsvp = manager.reserve_with_id(id.clone(), rsvp).await.unwrap();
+        assert_eq!(rsvp.id, id);
+
+        let rsvp = manager.get(id.clone()).await.unwrap();
+        assert_eq!(rsvp.id, id);
+
+        let err = manager
+            .reserve_with_id("not-a-uuid".to_string(), rsvp)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::InvalidReservationId("not-a-uuid".to_string())
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn duration_class_should_filter_and_count() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        for (rid, start, end) in [
+            (
+                "room-1",
+                "2022-12-25T10:00:00-0700",
+                "2022-12-25T10:30:00-0700",
+            ),
+            (
+                "room-2",
+                "2022-12-25T10:00:00-0700",
+                "2022-12-25T13:00:00-0700",
+            ),
+            (
+                "room-3",
+                "2022-12-25T10:00:00-0700",
+                "2022-12-25T15:00:00-0700",
+            ),
+            (
+                "room-4",
+                "2022-12-25T10:00:00-0700",
+                "2022-12-26T11:00:00-0700",
+            ),
+            (
+                "room-5",
+                "2022-12-20T10:00:00-0700",
+                "2022-12-26T10:00:00-0700",
+            ),
+        ] {
+            let rsvp = Reservation::new_pending(
+                "tyrId",
+                rid,
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            );
+            manager.reserve(rsvp).await.unwrap();
+        }
+        let query = |class: DurationClass| {
+            ReservationQueryBuilder::default()
+                .duration_class(class)
+                .start(
+                    "2022-12-01T00:00:00-0700"
+                        .parse::<prost_types::Timestamp>()
+                        .unwrap(),
+                )
+                .end(
+                    "2023-01-01T00:00:00-0700"
+                        .parse::<prost_types::Timestamp>()
+                        .unwrap(),
+                )
+                .status(ReservationStatus::Pending)
+                .build()
+                .unwrap()
+        };
+
+        let counts = manager
+            .duration_class_counts(query(DurationClass::Unspecified))
+            .await
+            .unwrap();
+        assert_eq!(counts[&DurationClass::SubHour], 1);
+        assert_eq!(counts[&DurationClass::Hourly], 2);
+        assert_eq!(counts[&DurationClass::Daily], 1);
+        assert_eq!(counts[&DurationClass::Multiday], 1);
+
+        let counts = manager
+            .duration_class_counts(query(DurationClass::Hourly))
+            .await
+            .unwrap();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[&DurationClass::Hourly], 2);
+
+        let rsvps = manager.query(query(DurationClass::Hourly)).await.unwrap();
+        let mut rids: Vec<_> = rsvps.into_iter().map(|r| r.resource_id).collect();
+        rids.sort();
+        assert_eq!(rids, vec!["room-2", "room-3"]);
+
+        let rsvps = manager
+            .query(query(DurationClass::Unspecified))
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 5);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_should_enforce_min_lead_time() {
+        let now: DateTime<FixedOffset> = "2022-12-25T08:00:00-0700".parse().unwrap();
+        let clock = crate::ManualClock::new(now.with_timezone(&Utc));
+        let manager = ReservationManager::new(migrated_pool.clone())
+            .with_clock(clock)
+            .with_min_lead_time(Duration::hours(2))
+            .with_resource_min_lead_time("walk-in", Duration::zero());
+        let rsvp = |rid: &str, start: &str| {
+            Reservation::new_pending(
+                "tyrId",
+                rid,
+                start.parse().unwrap(),
+                "2022-12-25T12:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+
+        let err = manager
+            .reserve(rsvp("1021", "2022-12-25T08:30:00-0700"))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::InsufficientLeadTime {
+                earliest: (now + Duration::hours(2)).with_timezone(&Utc)
+            }
+        );
+
+        assert!(manager
+            .reserve(rsvp("1021", "2022-12-25T10:00:00-0700"))
+            .await
+            .is_ok());
+        assert!(manager
+            .reserve(rsvp("walk-in", "2022-12-25T08:30:00-0700"))
+            .await
+            .is_ok());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn find_all_overlaps_should_report_planted_pair() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        assert!(manager.find_all_overlaps().await.unwrap().is_empty());
+
+        // simulate a bulk import with the constraint disabled
+        sqlx::query("ALTER TABLE rsvp.reservations DROP CONSTRAINT reservations_conflict")
+            .execute(&migrated_pool)
+            .await
+            .unwrap();
+        let (_, rsvp1) = make_tyr_reservation(&migrated_pool).await;
+        let (_, rsvp2) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "1021",
+            "2022-12-26T15:00:00-0700",
+            "2022-12-30T12:00:00-0700",
+            "",
+        )
+        .await;
+        // cancelled overlaps are not reported
+        let (_, rsvp3) = make_reservation(
+            &migrated_pool,
+            "bobId",
+            "1021",
+            "2022-12-27T15:00:00-0700",
+            "2022-12-29T12:00:00-0700",
+            "",
+        )
+        .await;
+        manager.cancel(rsvp3.id).await.unwrap();
+        let rsvp1 = manager.change_status(rsvp1.id, "").await.unwrap();
+        let rsvp2 = manager.change_status(rsvp2.id, "").await.unwrap();
+
+        let overlaps = manager.find_all_overlaps().await.unwrap();
+        assert_eq!(overlaps.len(), 1);
+        let (a, b) = &overlaps[0];
+        let mut ids = vec![a.id.clone(), b.id.clone()];
+        ids.sort();
+        let mut expected = vec![rsvp1.id, rsvp2.id];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn ending_soon_should_return_confirmed_reservations_in_window() {
+        let (manager, soon) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "1021",
+            "2022-12-25T08:00:00-0700",
+            "2022-12-25T10:30:00-0700",
+            "",
+        )
+        .await;
+        let (_, ended) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "1022",
+            "2022-12-25T08:00:00-0700",
+            "2022-12-25T09:30:00-0700",
+            "",
+        )
+        .await;
+        let (_, cancelled) = make_reservation(
+            &migrated_pool,
+            "bobId",
+            "1023",
+            "2022-12-25T08:00:00-0700",
+            "2022-12-25T10:30:00-0700",
+            "",
+        )
+        .await;
+        let (_, pending) = make_reservation(
+            &migrated_pool,
+            "bobId",
+            "1024",
+            "2022-12-25T08:00:00-0700",
+            "2022-12-25T10:30:00-0700",
+            "",
+        )
+        .await;
+        let soon = manager.change_status(soon.id, "").await.unwrap();
+        manager.change_status(ended.id, "").await.unwrap();
+        manager
+            .change_status(cancelled.id.clone(), "")
+            .await
+            .unwrap();
+        manager.cancel(cancelled.id).await.unwrap();
+
+        let now: DateTime<FixedOffset> = "2022-12-25T10:00:00-0700".parse().unwrap();
+        let rsvps = manager
+            .ending_soon(Duration::hours(1), now.with_timezone(&Utc))
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].id, soon.id);
+        assert_ne!(rsvps[0].id, pending.id);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_should_assign_increasing_references() {
+        let (manager, rsvp1) = make_tyr_reservation(&migrated_pool).await;
+        let (_, rsvp2) = make_alice_reservation(&migrated_pool).await;
+        assert!(rsvp1.reference > 0);
+        assert!(rsvp2.reference > rsvp1.reference);
+        assert_eq!(
+            rsvp1.reference_code(),
+            format!("RSVP-{:06}", rsvp1.reference)
+        );
+
+        let found = manager.get_by_reference(rsvp2.reference).await.unwrap();
+        assert_eq!(found.id, rsvp2.id);
+    }
+
+    #[derive(Debug)]
+    struct NoResourcePolicy(&'static str);
+
+    impl crate::ReservationPolicy for NoResourcePolicy {
+        fn check(&self, rsvp: &Reservation) -> Result<(), abi::Error> {
+            if rsvp.resource_id == self.0 {
+                return Err(abi::Error::PolicyViolation(format!(
+                    "resource {} is closed",
+                    self.0
+                )));
+            }
+            Ok(())
+        }
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_should_run_registered_policies() {
+        let manager = ReservationManager::new(migrated_pool.clone())
+            .with_policy(NoResourcePolicy("closed-room"))
+            .with_policy(crate::MaxDurationPolicy(Duration::days(7)));
+        let rsvp = |rid: &str, end: &str| {
+            Reservation::new_pending(
+                "tyrId",
+                rid,
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            )
+        };
+
+        let err = manager
+            .reserve(rsvp("closed-room", "2022-12-26T12:00:00-0700"))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::PolicyViolation("resource closed-room is closed".to_string())
+        );
+
+        let err = manager
+            .reserve(rsvp("1021", "2023-01-25T12:00:00-0700"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::PolicyViolation(_)));
+
+        assert!(manager
+            .reserve(rsvp("1021", "2022-12-26T12:00:00-0700"))
+            .await
+            .is_ok());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn availability_for_should_count_used_capacity_per_segment() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        manager.set_capacity("hall".into(), 3).await.unwrap();
+        make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "hall",
+            "2022-12-25T11:00:00-0700",
+            "2022-12-25T14:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "hall",
+            "2022-12-25T12:00:00-0700",
+            "2022-12-25T15:00:00-0700",
+            "",
+        )
+        .await;
+
+        let t = |s: &str| {
+            s.parse::<DateTime<FixedOffset>>()
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let slots = manager
+            .availability_for(
+                "hall".into(),
+                t("2022-12-25T10:00:00-0700"),
+                t("2022-12-25T16:00:00-0700"),
+                None,
+            )
+            .await
+            .unwrap();
+        let used: Vec<_> = slots.iter().map(|s| (s.start, s.end, s.used)).collect();
+        assert_eq!(
+            used,
+            vec![
+                (
+                    t("2022-12-25T10:00:00-0700"),
+                    t("2022-12-25T11:00:00-0700"),
+                    0
+                ),
+                (
+                    t("2022-12-25T11:00:00-0700"),
+                    t("2022-12-25T12:00:00-0700"),
+                    1
+                ),
+                (
+                    t("2022-12-25T12:00:00-0700"),
+                    t("2022-12-25T14:00:00-0700"),
+                    2
+                ),
+                (
+                    t("2022-12-25T14:00:00-0700"),
+                    t("2022-12-25T15:00:00-0700"),
+                    1
+                ),
+                (
+                    t("2022-12-25T15:00:00-0700"),
+                    t("2022-12-25T16:00:00-0700"),
+                    0
+                ),
+            ]
+        );
+        assert!(slots.iter().all(|s| s.capacity == 3));
+        assert_eq!(slots[2].remaining(), 1);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_beyond_capacity_should_reject() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        manager.set_capacity("hall".into(), 2).await.unwrap();
+        let rsvp = |uid: &str| {
+            Reservation::new_pending(
+                uid,
+                "hall",
+                "2022-12-25T11:00:00-0700".parse().unwrap(),
+                "2022-12-25T14:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+
+        manager.reserve(rsvp("tyrId")).await.unwrap();
+        manager.reserve(rsvp("aliceId")).await.unwrap();
+        let err = manager.reserve(rsvp("bobId")).await.unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::CapacityExceeded {
+                resource_id: "hall".to_string(),
+                capacity: 2
+            }
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn set_capacity_should_update_existing_reservations() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let rsvp = |uid: &str| {
+            Reservation::new_pending(
+                uid,
+                "hall",
+                "2022-12-25T11:00:00-0700".parse().unwrap(),
+                "2022-12-25T14:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+
+        // raising the capacity lets an existing exclusive booking share its slot
+        let first = manager.reserve(rsvp("tyrId")).await.unwrap();
+        manager.set_capacity("hall".into(), 2).await.unwrap();
+        let second = manager.reserve(rsvp("aliceId")).await.unwrap();
+
+        let err = manager.set_capacity("hall".into(), 1).await.unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::CapacityExceeded {
+                resource_id: "hall".to_string(),
+                capacity: 1
+            }
+        );
+
+        // lowering it makes the remaining booking exclusive again
+        manager.cancel(second.id).await.unwrap();
+        manager.set_capacity("hall".into(), 1).await.unwrap();
+        let shared: bool = sqlx::query_scalar("SELECT shared FROM rsvp.reservations WHERE id = $1")
+            .bind(Uuid::parse_str(&first.id).unwrap())
+            .fetch_one(&migrated_pool)
+            .await
+            .unwrap();
+        assert!(!shared);
+        let err = manager.reserve(rsvp("bobId")).await.unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_exact_retry_should_return_existing_reservation() {
+        let manager = ReservationManager::new(migrated_pool.clone()).with_exact_retry_dedupe(true);
+        let rsvp = |uid: &str| {
+            Reservation::new_pending(
+                uid,
+                "ocean-view-room-713",
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+
+        let first = manager.reserve(rsvp("tyrId")).await.unwrap();
+        let retry = manager.reserve(rsvp("tyrId")).await.unwrap();
+        assert!(retry.same_booking(&first));
+
+        let err = manager.reserve(rsvp("aliceId")).await.unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+
+        let rsvps = manager
+            .query(
+                ReservationQueryBuilder::default()
+                    .resource_id("ocean-view-room-713")
+                    .start(
+                        "2022-12-25T00:00:00-0700"
+                            .parse::<prost_types::Timestamp>()
+                            .unwrap(),
+                    )
+                    .end(
+                        "2022-12-30T00:00:00-0700"
+                            .parse::<prost_types::Timestamp>()
+                            .unwrap(),
+                    )
+                    .status(abi::ReservationStatus::Pending)
+                    .build()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 1);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn modified_by_should_attribute_status_changes_to_actor() {
+        let (manager, rsvp1) = make_tyr_reservation(&migrated_pool).await;
+        let (_, rsvp2) = make_alice_reservation(&migrated_pool).await;
+        manager
+            .change_status(rsvp1.id.clone(), "admin-x")
+            .await
+            .unwrap();
+        manager
+            .change_status(rsvp2.id.clone(), "admin-y")
+            .await
+            .unwrap();
+
+        let rsvps = manager.modified_by("admin-x", None).await.unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].id, rsvp1.id);
+
+        let future = Utc::now() + Duration::hours(1);
+        let rsvps = manager.modified_by("admin-x", Some(future)).await.unwrap();
+        assert!(rsvps.is_empty());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_bundle_should_book_all_or_nothing() {
+        let (manager, _) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "garden",
+            "2022-12-25T10:00:00-0700",
+            "2022-12-25T12:00:00-0700",
+            "",
+        )
+        .await;
+        let t = |s: &str| {
+            s.parse::<DateTime<FixedOffset>>()
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+
+        let err = manager
+            .reserve_bundle(
+                "tyrId".into(),
+                vec!["hall".into(), "garden".into()],
+                t("2022-12-25T11:00:00-0700"),
+                t("2022-12-25T18:00:00-0700"),
+                "wedding".into(),
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+        let query = ReservationQueryBuilder::default()
+            .resource_id("hall")
+            .start(
+                "2022-12-25T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-26T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(abi::ReservationStatus::Pending)
+            .build()
+            .unwrap();
+        assert!(manager.query(query).await.unwrap().is_empty());
+
+        let rsvps = manager
+            .reserve_bundle(
+                "tyrId".into(),
+                vec!["hall".into(), "garden".into()],
+                t("2022-12-25T13:00:00-0700"),
+                t("2022-12-25T18:00:00-0700"),
+                "wedding".into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 2);
+        assert!(!rsvps[0].bundle_id.is_empty());
+        assert_eq!(rsvps[0].bundle_id, rsvps[1].bundle_id);
+
+        let bundle = manager
+            .get_bundle(rsvps[0].bundle_id.clone())
+            .await
+            .unwrap();
+        let rids: Vec<_> = bundle.iter().map(|r| r.resource_id.as_str()).collect();
+        assert_eq!(rids, vec!["garden", "hall"]);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn relative_next_days_should_only_return_upcoming_week() {
+        let now: DateTime<FixedOffset> = "2022-12-22T09:00:00-0700".parse().unwrap();
+        let manager = ReservationManager::new(migrated_pool.clone())
+            .with_clock(crate::ManualClock::new(now.with_timezone(&Utc)));
+        // falls within the next week
+        let (_, soon) = make_tyr_reservation(&migrated_pool).await;
+        // starts more than a week out
+        make_alice_reservation(&migrated_pool).await;
+
+        // start and end are replaced by the relative window
+        let filter = abi::ReservationQuery {
+            status: abi::ReservationStatus::Pending as i32,
+            ..Default::default()
+        };
+        let rsvps = manager
+            .relative(RelativeWindow::NextDays(7), None, filter.clone())
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].id, soon.id);
+
+        let rsvps = manager
+            .relative(RelativeWindow::LastDays(7), None, filter)
+            .await
+            .unwrap();
+        assert!(rsvps.is_empty());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_far_future_time_should_fail_cleanly() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let mut rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        rsvp.end_time = Some(prost_types::Timestamp {
+            seconds: 9_223_372_036_854_775,
+            nanos: 0,
+        });
+
+        let err = manager.reserve(rsvp).await.unwrap_err();
+        assert_eq!(err, abi::Error::TimeOutOfRange("end".to_string()));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn first_and_last_should_return_earliest_and_latest() {
+        let (manager, earliest) = make_tyr_reservation(&migrated_pool).await;
+        let (_, latest) = make_alice_reservation(&migrated_pool).await;
+        make_reservation(
+            &migrated_pool,
+            "bobId",
+            "1021",
+            "2023-01-05T15:00:00-0700",
+            "2023-01-08T12:00:00-0700",
+            "",
+        )
+        .await;
+        let query = ReservationQueryBuilder::default()
+            .resource_id("1021")
+            .start(
+                "2022-12-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2023-03-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(abi::ReservationStatus::Pending)
+            .build()
+            .unwrap();
+
+        let first = manager.first(query.clone()).await.unwrap().unwrap();
+        assert_eq!(first.id, earliest.id);
+        let last = manager.last(query.clone()).await.unwrap().unwrap();
+        assert_eq!(last.id, latest.id);
+
+        let mut desc = query.clone();
+        desc.desc = true;
+        let first = manager.first(desc).await.unwrap().unwrap();
+        assert_eq!(first.id, latest.id);
+
+        let mut empty = query;
+        empty.user_id = "nobody".to_string();
+        assert!(manager.first(empty).await.unwrap().is_none());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reassign_resource_should_move_compatible_bookings() {
+        let (manager, rsvp1) = make_tyr_reservation(&migrated_pool).await;
+        let (_, rsvp2) = make_alice_reservation(&migrated_pool).await;
+
+        let moved = manager
+            .reassign_resource("1021", "2021", None)
+            .await
+            .unwrap();
+        assert_eq!(moved, 2);
+        assert_eq!(manager.get(rsvp1.id).await.unwrap().resource_id, "2021");
+        assert_eq!(manager.get(rsvp2.id).await.unwrap().resource_id, "2021");
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reassign_resource_onto_itself_should_be_rejected() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool).await;
+
+        let err = manager
+            .reassign_resource("1021", "1021", None)
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::InvalidResourceId("1021".into()));
+        assert_eq!(manager.get(rsvp.id).await.unwrap().resource_id, "1021");
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reassign_resource_onto_overlapping_booking_should_roll_back() {
+        let (manager, rsvp1) = make_tyr_reservation(&migrated_pool).await;
+        let (_, rsvp2) = make_alice_reservation(&migrated_pool).await;
+        make_reservation(
+            &migrated_pool,
+            "bobId",
+            "2021",
+            "2023-02-01T15:00:00-0700",
+            "2023-02-03T12:00:00-0700",
+            "",
+        )
+        .await;
+
+        let err = manager
+            .reassign_resource("1021", "2021", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+        assert_eq!(manager.get(rsvp1.id).await.unwrap().resource_id, "1021");
+        assert_eq!(manager.get(rsvp2.id).await.unwrap().resource_id, "1021");
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reassign_resource_should_check_target_capacity_and_blackouts() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        manager.set_capacity("hall".into(), 2).await.unwrap();
+        let mut shared = vec![];
+        for uid in ["tyrId", "aliceId"] {
+            let rsvp = Reservation::new_pending(
+                uid,
+                "hall",
+                "2022-12-25T11:00:00-0700".parse().unwrap(),
+                "2022-12-25T14:00:00-0700".parse().unwrap(),
+                "",
+            );
+            shared.push(manager.reserve(rsvp).await.unwrap());
+        }
+
+        // "room" has the default capacity of 1
+        let err = manager
+            .reassign_resource("hall", "room", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+        for rsvp in &shared {
+            let rsvp = manager.get(rsvp.id.clone()).await.unwrap();
+            assert_eq!(rsvp.resource_id, "hall");
+        }
+
+        manager.cancel(shared[1].id.clone()).await.unwrap();
+        manager
+            .add_blackout(
+                "room".into(),
+                "2022-12-25T13:00:00-0700".parse().unwrap(),
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        let err = manager
+            .reassign_resource("hall", "room", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+
+        let moved = manager
+            .reassign_resource("hall", "room-2", None)
+            .await
+            .unwrap();
+        assert_eq!(moved, 2);
+        let shared_flag: bool =
+            sqlx::query_scalar("SELECT shared FROM rsvp.reservations WHERE id = $1")
+                .bind(Uuid::parse_str(&shared[0].id).unwrap())
+                .fetch_one(&migrated_pool)
+                .await
+                .unwrap();
+        assert!(!shared_flag);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn calendar_events_should_color_by_status() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool).await;
+        let query = ReservationQueryBuilder::default()
+            .user_id("tyrId")
+            .start(
+                "2022-12-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2023-01-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(abi::ReservationStatus::Pending)
+            .build()
+            .unwrap();
+
+        let events = manager.calendar_events(query.clone()).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].id, rsvp.id);
+        assert_eq!(events[0].title, "tyrId @ 1021");
+        assert_eq!(events[0].color, CalendarEvent::PENDING_COLOR);
+
+        let confirmed = manager.change_status(rsvp.id, "").await.unwrap();
+        let mut query = query;
+        query.status = abi::ReservationStatus::Confirmed as i32;
+        let events = manager.calendar_events(query).await.unwrap();
+        assert_eq!(events[0].color, CalendarEvent::CONFIRMED_COLOR);
+
+        let mut blocked = confirmed;
+        blocked.status = abi::ReservationStatus::Blocked as i32;
+        let event = CalendarEvent::try_from(blocked).unwrap();
+        assert_eq!(event.color, CalendarEvent::BLOCKED_COLOR);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_over_user_quota_should_reject() {
+        let manager = ReservationManager::new(migrated_pool.clone()).with_max_active_per_user(3);
+        let rsvp = |rid: &str| {
+            Reservation::new_pending(
+                "tyrId",
+                rid,
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+
+        let first = manager.reserve(rsvp("1")).await.unwrap();
+        manager.reserve(rsvp("2")).await.unwrap();
+        manager.reserve(rsvp("3")).await.unwrap();
+        let err = manager.reserve(rsvp("4")).await.unwrap_err();
+        assert_eq!(err, abi::Error::UserQuotaExceeded { max: 3 });
+
+        // cancelled reservations don't count
+        manager.cancel(first.id).await.unwrap();
+        manager.reserve(rsvp("4")).await.unwrap();
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn bulk_import_should_report_overlaps() {
+        let (manager, stored) = make_reservation(
+            &migrated_pool,
+            "daveId",
+            "1023",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-28T12:00:00-0700",
+            "",
+        )
+        .await;
+        let rsvp = |uid: &str, rid: &str, start: &str, end: &str| {
+            Reservation::new_pending(
+                uid,
+                rid,
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "imported, \"quoted\"",
+            )
+        };
+        let mut confirmed = rsvp(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-28T12:00:00-0700",
+        );
+        confirmed.status = abi::ReservationStatus::Confirmed as i32;
+        let rsvps = vec![
+            confirmed,
+            // pending rows are covered by the constraint too
+            rsvp(
+                "aliceId",
+                "1021",
+                "2022-12-27T15:00:00-0700",
+                "2022-12-30T12:00:00-0700",
+            ),
+            rsvp(
+                "bobId",
+                "1022",
+                "2022-12-25T15:00:00-0700",
+                "2022-12-28T12:00:00-0700",
+            ),
+            // overlaps a stored reservation
+            rsvp(
+                "carolId",
+                "1023",
+                "2022-12-26T15:00:00-0700",
+                "2022-12-27T12:00:00-0700",
+            ),
+        ];
+
+        let report = manager
+            .bulk_import(futures::stream::iter(rsvps))
+            .await
+            .unwrap();
+        assert_eq!(report.imported, 4);
+        assert!(report.constraint_dropped);
+        assert_eq!(report.overlaps.len(), 2);
+        let (a, b) = &report.overlaps[0];
+        assert_eq!(
+            (a.user_id.as_str(), b.user_id.as_str()),
+            ("tyrId", "aliceId")
+        );
+        assert_eq!(a.note, "imported, \"quoted\"");
+        assert_eq!(a.created_by, "");
+        let (a, b) = &report.overlaps[1];
+        assert_eq!(a.user_id, "carolId");
+        assert_eq!(b.id, stored.id);
+
+        let query = ReservationQueryBuilder::default()
+            .start(
+                "2022-12-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2023-01-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .build()
+            .unwrap();
+        let mut users: Vec<_> = manager
+            .query(query)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.user_id)
+            .collect();
+        users.sort();
+        assert_eq!(users, vec!["aliceId", "bobId", "carolId", "daveId"]);
+        assert_eq!(manager.find_all_overlaps().await.unwrap().len(), 2);
+        assert!(manager.verify_constraints().await.is_err());
+
+        // resolved overlaps let the constraint back in
+        assert!(manager.restore_conflict_constraint().await.is_err());
+        for (_, b) in &report.overlaps {
+            manager.cancel(b.id.clone()).await.unwrap();
+        }
+        manager.restore_conflict_constraint().await.unwrap();
+        manager.verify_constraints().await.unwrap();
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn bulk_import_should_keep_all_columns_and_share_by_capacity() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        manager.set_capacity("hall".into(), 2).await.unwrap();
+        let rsvp = |uid: &str| abi::Reservation {
+            units: Some(1),
+            party_size: Some(4),
+            expires_at: Some(abi::convert_to_timestamp(
+                "2022-12-20T00:00:00Z".parse().unwrap(),
+            )),
+            amount_cents: Some(1200),
+            currency: Some("USD".into()),
+            special_requests: vec!["window \"seat\"".into(), "back\\slash".into()],
+            ..Reservation::new_pending(
+                uid,
+                "hall",
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+
+        let report = manager
+            .bulk_import(futures::stream::iter(vec![rsvp("tyrId"), rsvp("aliceId")]))
+            .await
+            .unwrap();
+        assert_eq!(report.imported, 2);
+        assert!(report.overlaps.is_empty());
+        assert!(report.over_capacity.is_empty());
+
+        let stored: Vec<abi::Reservation> =
+            sqlx::query_as("SELECT * FROM rsvp.reservations WHERE shared ORDER BY user_id")
+                .fetch_all(&migrated_pool)
+                .await
+                .unwrap();
+        assert_eq!(stored.len(), 2);
+        let expected = rsvp("aliceId");
+        assert_eq!(stored[0].units, expected.units);
+        assert_eq!(stored[0].party_size, expected.party_size);
+        assert_eq!(stored[0].expires_at, expected.expires_at);
+        assert_eq!(stored[0].amount_cents, expected.amount_cents);
+        assert_eq!(stored[0].currency, expected.currency);
+        assert_eq!(stored[0].special_requests, expected.special_requests);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn bulk_import_should_report_capacity_overruns() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        manager.set_capacity("hall".into(), 2).await.unwrap();
+        let rsvp = |uid: &str, start: &str, end: &str| {
+            Reservation::new_pending(
+                uid,
+                "hall",
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            )
+        };
+        let rsvps = vec![
+            rsvp("tyrId", "2022-12-25T15:00:00Z", "2022-12-25T18:00:00Z"),
+            rsvp("aliceId", "2022-12-25T15:00:00Z", "2022-12-25T18:00:00Z"),
+            // a third seat from 17:00
+            rsvp("bobId", "2022-12-25T17:00:00Z", "2022-12-25T19:00:00Z"),
+            rsvp("carolId", "2022-12-25T20:00:00Z", "2022-12-25T21:00:00Z"),
+        ];
+
+        let report = manager
+            .bulk_import(futures::stream::iter(rsvps))
+            .await
+            .unwrap();
+        assert_eq!(report.imported, 4);
+        assert!(report.overlaps.is_empty());
+        assert!(!report.constraint_dropped);
+        let users: Vec<_> = report
+            .over_capacity
+            .iter()
+            .map(|r| r.user_id.as_str())
+            .collect();
+        assert_eq!(users, vec!["tyrId", "aliceId", "bobId"]);
+        manager.verify_constraints().await.unwrap();
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn bulk_import_without_overlaps_should_restore_constraint() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let rsvps = vec![Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        )];
+
+        let report = manager
+            .bulk_import(futures::stream::iter(rsvps))
+            .await
+            .unwrap();
+        assert_eq!(report.imported, 1);
+        assert!(report.overlaps.is_empty());
+        assert!(!report.constraint_dropped);
+
+        let err = manager
+            .reserve(Reservation::new_pending(
+                "aliceId",
+                "1021",
+                "2022-12-26T15:00:00-0700".parse().unwrap(),
+                "2022-12-27T12:00:00-0700".parse().unwrap(),
+                "",
+            ))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn recurring_availability_should_follow_dst_and_report_conflicts() {
+        let (manager, _) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "1021",
+            "2022-11-14T10:30:00-0700",
+            "2022-11-14T11:30:00-0700",
+            "",
+        )
+        .await;
+        let rule = RecurrenceRule {
+            start: "2022-10-31T10:00:00".parse().unwrap(),
+            duration: Duration::hours(1),
+            frequency: crate::Frequency::Weekly,
+            tz: chrono_tz::America::Denver,
+        };
+
+        let slots = manager
+            .recurring_availability("1021".into(), rule, 3)
+            .await
+            .unwrap();
+        let t = |s: &str| {
+            s.parse::<DateTime<FixedOffset>>()
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        assert_eq!(
+            slots,
+            vec![
+                // still on daylight saving time
+                (
+                    (t("2022-10-31T10:00:00-0600"), t("2022-10-31T11:00:00-0600")),
+                    true
+                ),
+                (
+                    (t("2022-11-07T10:00:00-0700"), t("2022-11-07T11:00:00-0700")),
+                    true
+                ),
+                (
+                    (t("2022-11-14T10:00:00-0700"), t("2022-11-14T11:00:00-0700")),
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn recurring_availability_should_report_blackouts() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        manager
+            .add_blackout(
+                "1021".into(),
+                "2022-11-07T00:00:00-0700".parse().unwrap(),
+                "2022-11-08T00:00:00-0700".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+        let rule = RecurrenceRule {
+            start: "2022-10-31T10:00:00".parse().unwrap(),
+            duration: Duration::hours(1),
+            frequency: crate::Frequency::Weekly,
+            tz: chrono_tz::America::Denver,
+        };
+
+        let slots = manager
+            .recurring_availability("1021".into(), rule, 3)
+            .await
+            .unwrap();
+        let free: Vec<_> = slots.into_iter().map(|(_, free)| free).collect();
+        assert_eq!(free, vec![true, false, true]);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn mutations_should_write_outbox_events() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool).await;
+        // a rejected reserve must not leave an event behind
+        let conflicting = Reservation::new_pending(
+            "aliceId",
+            "1021",
+            "2022-12-26T15:00:00-0700".parse().unwrap(),
+            "2022-12-27T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        manager.reserve(conflicting).await.unwrap_err();
+        manager.change_status(rsvp.id.clone(), "").await.unwrap();
+        manager.cancel(rsvp.id.clone()).await.unwrap();
+
+        let events = manager.drain_outbox(2).await.unwrap();
+        let kinds: Vec<_> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![OutboxEventKind::Reserved, OutboxEventKind::Confirmed]
+        );
+        assert!(events[0].reservation.same_booking(&rsvp));
+        assert_eq!(
+            events[1].reservation.status,
+            abi::ReservationStatus::Confirmed as i32
+        );
+
+        let events = manager.drain_outbox(10).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, OutboxEventKind::Cancelled);
+        assert_eq!(events[0].reservation.id, rsvp.id);
+        assert!(manager.drain_outbox(10).await.unwrap().is_empty());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn utilization_should_clamp_bookings_to_window() {
+        let (manager, rsvp1) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-12-24T20:00:00-0700",
+            "2022-12-25T08:00:00-0700",
+            "",
+        )
+        .await;
+        let (_, rsvp2) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "713",
+            "2022-12-25T10:00:00-0700",
+            "2022-12-25T18:24:00-0700",
+            "",
+        )
+        .await;
+        // pending bookings don't count
+        make_reservation(
+            &migrated_pool,
+            "bobId",
+            "713",
+            "2022-12-25T20:00:00-0700",
+            "2022-12-25T22:00:00-0700",
+            "",
+        )
+        .await;
+        manager.change_status(rsvp1.id, "").await.unwrap();
+        manager.change_status(rsvp2.id, "").await.unwrap();
+
+        let t = |s: &str| {
+            s.parse::<DateTime<FixedOffset>>()
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let ratio = manager
+            .utilization(
+                "713".into(),
+                t("2022-12-25T02:00:00-0700"),
+                t("2022-12-26T02:00:00-0700"),
+                None,
+            )
+            .await
+            .unwrap();
+        // 6h + 8.4h of 24h
+        assert!((ratio - 0.6).abs() < 1e-9);
+
+        manager.set_capacity("713".into(), 2).await.unwrap();
+        let ratio = manager
+            .utilization(
+                "713".into(),
+                t("2022-12-25T02:00:00-0700"),
+                t("2022-12-26T02:00:00-0700"),
+                None,
+            )
+            .await
+            .unwrap();
+        assert!((ratio - 0.3).abs() < 1e-9);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn hold_should_expire_after_ttl() {
+        let now: DateTime<FixedOffset> = "2022-12-20T09:00:00-0700".parse().unwrap();
+        let clock = crate::ManualClock::new(now.with_timezone(&Utc));
+        let manager = ReservationManager::new(migrated_pool.clone()).with_clock(clock.clone());
+        let rsvp = |uid: &str, rid: &str| {
+            Reservation::new_pending(
+                uid,
+                rid,
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+
+        let lapsing = manager
+            .hold(rsvp("tyrId", "1021"), Duration::minutes(10))
+            .await
+            .unwrap();
+        assert_eq!(
+            lapsing.expires_at,
+            Some(abi::convert_to_timestamp(
+                (now + Duration::minutes(10)).with_timezone(&Utc)
+            ))
+        );
+        let kept = manager
+            .hold(rsvp("aliceId", "1022"), Duration::minutes(10))
+            .await
+            .unwrap();
+        let kept = manager.change_status(kept.id, "").await.unwrap();
+        assert_eq!(kept.expires_at, None);
+
+        clock.advance(Duration::minutes(5));
+        assert!(manager.expire_holds().await.unwrap().is_empty());
+
+        clock.advance(Duration::minutes(6));
+        let err = manager
+            .change_status(lapsing.id.clone(), "")
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::NotFound);
+        let expired = manager.expire_holds().await.unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, lapsing.id);
+        assert_eq!(expired[0].status, abi::ReservationStatus::Cancelled as i32);
+
+        // the window is free again
+        manager.reserve(rsvp("bobId", "1021")).await.unwrap();
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn hold_with_auto_confirm_should_still_expire() {
+        let now: DateTime<Utc> = "2022-12-20T16:00:00Z".parse().unwrap();
+        let clock = crate::ManualClock::new(now);
+        let manager = ReservationManager::new(migrated_pool.clone())
+            .with_clock(clock.clone())
+            .with_auto_confirm(true);
+        let rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+
+        let held = manager.hold(rsvp, Duration::minutes(10)).await.unwrap();
+        assert_eq!(held.status, abi::ReservationStatus::Pending as i32);
+
+        clock.advance(Duration::minutes(11));
+        let expired = manager.expire_holds().await.unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, held.id);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn user_schedule_conflicts_should_report_cross_resource_overlaps() {
+        let (manager, rsvp1) = make_tyr_reservation(&migrated_pool).await;
+        let (_, rsvp2) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "1022",
+            "2022-12-27T15:00:00-0700",
+            "2022-12-29T12:00:00-0700",
+            "",
+        )
+        .await;
+        // not overlapping, and another user's overlap
+        make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "1023",
+            "2023-01-27T15:00:00-0700",
+            "2023-01-29T12:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "1024",
+            "2022-12-27T15:00:00-0700",
+            "2022-12-29T12:00:00-0700",
+            "",
+        )
+        .await;
+
+        let pairs = manager
+            .user_schedule_conflicts("tyrId".into())
+            .await
+            .unwrap();
+        assert_eq!(pairs.len(), 1);
+        let mut ids = vec![pairs[0].0.id.clone(), pairs[0].1.id.clone()];
+        ids.sort();
+        let mut expected = vec![rsvp1.id, rsvp2.id];
+        expected.sort();
+        assert_eq!(ids, expected);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_should_keep_minute_precision() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let windows = [
+            ("2022-12-25T15:07:00-0700", "2022-12-25T16:23:00-0700"),
+            ("2022-12-26T09:23:00+0545", "2022-12-26T09:59:00+0545"),
+            ("2022-12-27T23:59:00+0000", "2022-12-28T00:07:00+0000"),
+        ];
+
+        for (start, end) in windows {
+            let start: DateTime<FixedOffset> = start.parse().unwrap();
+            let end: DateTime<FixedOffset> = end.parse().unwrap();
+            let rsvp = manager
+                .reserve(Reservation::new_pending("tyrId", "1021", start, end, ""))
+                .await
+                .unwrap();
+
+            let stored = manager.get(rsvp.id).await.unwrap();
+            assert_eq!(
+                abi::to_datetime(stored.start_time.as_ref()).unwrap(),
+                start.with_timezone(&Utc)
+            );
+            assert_eq!(
+                abi::to_datetime(stored.end_time.as_ref()).unwrap(),
+                end.with_timezone(&Utc)
+            );
+        }
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn distinct_user_count_should_count_each_user_once() {
+        let (manager, _) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-01-05T15:00:00-0700",
+            "2022-01-06T12:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-03-05T15:00:00-0700",
+            "2022-03-06T12:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "713",
+            "2022-06-05T15:00:00-0700",
+            "2022-06-06T12:00:00-0700",
+            "",
+        )
+        .await;
+        let mut maintenance = Reservation::new_pending(
+            "system",
+            "713",
+            "2022-08-05T15:00:00-0700".parse().unwrap(),
+            "2022-08-06T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        maintenance.status = abi::ReservationStatus::Blocked as i32;
+        manager.reserve(maintenance).await.unwrap();
+
+        let count = manager
+            .distinct_user_count("713".into(), None, false)
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+        let count = manager
+            .distinct_user_count("713".into(), None, true)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+
+        let t = |s: &str| {
+            s.parse::<DateTime<FixedOffset>>()
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let count = manager
+            .distinct_user_count(
+                "713".into(),
+                Some((t("2022-02-01T00:00:00-0700"), t("2023-01-01T00:00:00-0700"))),
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn revenue_should_sum_confirmed_amounts() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let priced = |uid: &str, rid: &str, amount: i64| {
+            let mut rsvp = Reservation::new_pending(
+                uid,
+                rid,
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-28T12:00:00-0700".parse().unwrap(),
+                "",
+            );
+            rsvp.amount_cents = Some(amount);
+            rsvp.currency = Some("USD".to_string());
+            rsvp
+        };
+
+        let rsvp1 = manager
+            .reserve(priced("tyrId", "1021", 12_500))
+            .await
+            .unwrap();
+        let rsvp2 = manager
+            .reserve(priced("aliceId", "1022", 7_550))
+            .await
+            .unwrap();
+        // still pending
+        manager
+            .reserve(priced("bobId", "1023", 1_000))
+            .await
+            .unwrap();
+        let stored = manager.change_status(rsvp1.id, "").await.unwrap();
+        assert_eq!(stored.amount_cents, Some(12_500));
+        assert_eq!(stored.currency.as_deref(), Some("USD"));
+        manager.change_status(rsvp2.id, "").await.unwrap();
+
+        let query = ReservationQueryBuilder::default()
+            .start(
+                "2022-12-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2023-01-01T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(manager.revenue(query.clone()).await.unwrap(), 20_050);
+
+        let query = abi::ReservationQuery {
+            search: Some("alice".into()),
+            ..query
+        };
+        assert_eq!(manager.revenue(query).await.unwrap(), 7_550);
+
+        let mut invalid = priced("carolId", "1024", 100);
+        invalid.currency = Some("dollars".to_string());
+        let err = manager.reserve(invalid).await.unwrap_err();
+        assert_eq!(err, abi::Error::InvalidCurrency("dollars".to_string()));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn find_incomplete_should_return_rows_missing_field() {
+        let (manager, legacy) = make_tyr_reservation(&migrated_pool).await;
+        let mut rsvp = Reservation::new_pending(
+            "aliceId",
+            "1022",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        rsvp.created_by = "agent-a".to_string();
+        rsvp.amount_cents = Some(100);
+        let complete = manager.reserve(rsvp).await.unwrap();
+
+        let rsvps = manager
+            .find_incomplete(RequiredField::CreatedBy)
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].id, legacy.id);
+
+        let rsvps = manager
+            .find_incomplete(RequiredField::Currency)
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 2);
+        assert!(rsvps.iter().any(|r| r.id == complete.id));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_weekdays_should_match_local_start_day() {
+        // Friday, Saturday and Sunday evenings in -0700, i.e. a day later in UTC
+        let (manager, friday) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-12-23T20:00:00-0700",
+            "2022-12-23T22:00:00-0700",
+            "",
+        )
+        .await;
+        let (_, saturday) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-12-24T20:00:00-0700",
+            "2022-12-24T22:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-12-25T20:00:00-0700",
+            "2022-12-25T22:00:00-0700",
+            "",
+        )
+        .await;
+
+        let query = ReservationQueryBuilder::default()
+            .resource_id("713")
+            .start(
+                "2022-12-20T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-30T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(abi::ReservationStatus::Pending)
+            .on_weekdays(
+                &[chrono::Weekday::Fri, chrono::Weekday::Sat],
+                "America/Denver",
+            )
+            .build()
+            .unwrap();
+        let rsvps = manager.query(query.clone()).await.unwrap();
+        let ids: Vec<_> = rsvps.iter().map(|r| r.id.clone()).collect();
+        assert_eq!(ids, vec![friday.id, saturday.id.clone()]);
+
+        // in UTC the Friday booking starts on Saturday
+        let mut utc = query.clone();
+        utc.weekday_tz = "".to_string();
+        utc.weekdays = vec![chrono::Weekday::Fri.num_days_from_sunday() as i32];
+        assert!(manager.query(utc).await.unwrap().is_empty());
+
+        let mut invalid = query;
+        invalid.weekday_tz = "Mars/Olympus_Mons".to_string();
+        let err = manager.query(invalid).await.unwrap_err();
+        assert!(matches!(err, abi::Error::InvalidQuery(_)));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_over_hours_quota_should_reject() {
+        let now: DateTime<FixedOffset> = "2022-12-19T00:00:00-0700".parse().unwrap();
+        let manager = ReservationManager::new(migrated_pool.clone())
+            .with_clock(crate::ManualClock::new(now.with_timezone(&Utc)))
+            .with_hours_quota(Duration::hours(10), Duration::weeks(1));
+        let rsvp = |rid: &str, start: &str, end: &str| {
+            Reservation::new_pending(
+                "tyrId",
+                rid,
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            )
+        };
+
+        manager
+            .reserve(rsvp(
+                "1021",
+                "2022-12-20T09:00:00-0700",
+                "2022-12-20T15:00:00-0700",
+            ))
+            .await
+            .unwrap();
+        manager
+            .reserve(rsvp(
+                "1022",
+                "2022-12-21T09:00:00-0700",
+                "2022-12-21T12:00:00-0700",
+            ))
+            .await
+            .unwrap();
+        let err = manager
+            .reserve(rsvp(
+                "1023",
+                "2022-12-22T09:00:00-0700",
+                "2022-12-22T11:00:00-0700",
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::HoursQuotaExceeded {
+                max: Duration::hours(10),
+                would_be: Duration::hours(11)
+            }
+        );
+
+        // exactly at the cap, and outside the period
+        manager
+            .reserve(rsvp(
+                "1023",
+                "2022-12-22T09:00:00-0700",
+                "2022-12-22T10:00:00-0700",
+            ))
+            .await
+            .unwrap();
+        manager
+            .reserve(rsvp(
+                "1023",
+                "2023-01-22T09:00:00-0700",
+                "2023-01-22T19:00:00-0700",
+            ))
+            .await
+            .unwrap();
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn user_free_gaps_should_complement_merged_bookings() {
+        let (manager, _) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "1021",
+            "2022-12-25T09:00:00-0700",
+            "2022-12-25T11:00:00-0700",
+            "",
+        )
+        .await;
+        // overlaps the first booking on another resource
+        make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "1022",
+            "2022-12-25T10:00:00-0700",
+            "2022-12-25T12:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "1023",
+            "2022-12-25T14:00:00-0700",
+            "2022-12-25T15:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "1024",
+            "2022-12-25T12:00:00-0700",
+            "2022-12-25T13:00:00-0700",
+            "",
+        )
+        .await;
+
+        let t = |s: &str| {
+            s.parse::<DateTime<FixedOffset>>()
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let gaps = manager
+            .user_free_gaps(
+                "tyrId".into(),
+                t("2022-12-25T08:00:00-0700"),
+                t("2022-12-25T18:00:00-0700"),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            gaps,
+            vec![
+                (t("2022-12-25T08:00:00-0700"), t("2022-12-25T09:00:00-0700")),
+                (t("2022-12-25T12:00:00-0700"), t("2022-12-25T14:00:00-0700")),
+                (t("2022-12-25T15:00:00-0700"), t("2022-12-25T18:00:00-0700")),
+            ]
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_validated_should_report_all_problems() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let mut rsvp = Reservation::new_pending(
+            "",
+            "",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        rsvp.currency = Some("usd".to_string());
+
+        let err = manager.reserve_validated(rsvp).await.unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::InvalidReservation(vec![
+                abi::Error::InvalidUserId("".into()),
+                abi::Error::InvalidResourceId("".into()),
+                abi::Error::InvalidCurrency("usd".into()),
+            ])
+        );
+
+        let (_, rsvp) = make_tyr_reservation(&migrated_pool).await;
+        assert!(!rsvp.id.is_empty());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn quote_should_blend_tiers_across_boundaries() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let off_peak = PricingRule {
+            weekday: None,
+            start_hour: 0,
+            end_hour: 24,
+            rate_cents: 1000,
+        };
+        let peak = PricingRule {
+            weekday: Some(chrono::Weekday::Fri),
+            start_hour: 17,
+            end_hour: 21,
+            rate_cents: 3000,
+        };
+        manager
+            .add_pricing_rule("901".into(), off_peak)
+            .await
+            .unwrap();
+        manager.add_pricing_rule("901".into(), peak).await.unwrap();
+
+        // friday 16:00 - 18:30 UTC: one off-peak hour, one and a half peak hours
+        let start = "2022-12-30T16:00:00Z".parse().unwrap();
+        let end = "2022-12-30T18:30:00Z".parse().unwrap();
+        let price = manager.quote("901".into(), start, end).await.unwrap();
+        assert_eq!(price, 1000 + 4500);
+
+        // the peak rule is friday only
+        let start = "2022-12-31T16:00:00Z".parse().unwrap();
+        let end = "2022-12-31T18:30:00Z".parse().unwrap();
+        let price = manager.quote("901".into(), start, end).await.unwrap();
+        assert_eq!(price, 2500);
+
+        let err = manager
+            .add_pricing_rule(
+                "901".into(),
+                PricingRule {
+                    start_hour: 21,
+                    end_hour: 17,
+                    ..peak
+                },
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::InvalidPricingRule("hours 21..17".into()));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn quote_should_price_far_future_windows() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let flat = PricingRule {
+            weekday: None,
+            start_hour: 0,
+            end_hour: 24,
+            rate_cents: 1000,
+        };
+        manager.add_pricing_rule("901".into(), flat).await.unwrap();
+
+        // past the nanosecond timestamp range
+        let start = "2300-01-01T00:15:00Z".parse().unwrap();
+        let end = "2300-01-01T02:45:00Z".parse().unwrap();
+        let price = manager.quote("901".into(), start, end).await.unwrap();
+        assert_eq!(price, 2500);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn compact_schedule_should_close_gaps() {
+        let (manager, a) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "room-1",
+            "2022-12-25T10:00:00Z",
+            "2022-12-25T11:00:00Z",
+            "",
+        )
+        .await;
+        let (_, b) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "room-1",
+            "2022-12-25T13:00:00Z",
+            "2022-12-25T15:00:00Z",
+            "",
+        )
+        .await;
+        manager.change_status(a.id.clone(), "").await.unwrap();
+        manager.change_status(b.id.clone(), "").await.unwrap();
+
+        let from: DateTime<Utc> = "2022-12-25T08:00:00Z".parse().unwrap();
+        let rsvps = manager
+            .compact_schedule("room-1".into(), from)
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 2);
+        assert_eq!(rsvps[0].id, a.id);
+        assert_eq!(rsvps[1].id, b.id);
+
+        let ts = |s: &str| abi::convert_to_timestamp(s.parse().unwrap());
+        assert_eq!(rsvps[0].start_time, Some(ts("2022-12-25T08:00:00Z")));
+        assert_eq!(rsvps[0].end_time, Some(ts("2022-12-25T09:00:00Z")));
+        assert_eq!(rsvps[1].start_time, Some(ts("2022-12-25T09:00:00Z")));
+        assert_eq!(rsvps[1].end_time, Some(ts("2022-12-25T11:00:00Z")));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn compact_schedule_should_skip_blackouts() {
+        let (manager, a) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "room-1",
+            "2022-12-25T10:00:00Z",
+            "2022-12-25T11:00:00Z",
+            "",
+        )
+        .await;
+        manager.change_status(a.id.clone(), "").await.unwrap();
+        manager
+            .add_blackout(
+                "room-1".into(),
+                "2022-12-25T08:00:00Z".parse().unwrap(),
+                "2022-12-25T09:30:00Z".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let from: DateTime<Utc> = "2022-12-25T08:00:00Z".parse().unwrap();
+        let rsvps = manager
+            .compact_schedule("room-1".into(), from)
+            .await
+            .unwrap();
+
+        let ts = |s: &str| abi::convert_to_timestamp(s.parse().unwrap());
+        assert_eq!(rsvps[0].start_time, Some(ts("2022-12-25T09:30:00Z")));
+        assert_eq!(rsvps[0].end_time, Some(ts("2022-12-25T10:30:00Z")));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn update_if_version_should_reject_stale_version() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool).await;
+        assert_eq!(rsvp.version, 1);
+
+        let changes = ReservationChanges {
+            note: Some("first edit".into()),
+            ..Default::default()
+        };
+        let rsvp = manager
+            .update_if_version(rsvp.id, 1, changes)
+            .await
+            .unwrap();
+        assert_eq!(rsvp.note, "first edit");
+        assert_eq!(rsvp.version, 2);
+
+        // a second form still holding version 1
+        let changes = ReservationChanges {
+            note: Some("stale edit".into()),
+            ..Default::default()
+        };
+        let err = manager
+            .update_if_version(rsvp.id.clone(), 1, changes.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::VersionConflict { current: 2 });
+        assert_eq!(
+            manager.get(rsvp.id.clone()).await.unwrap().note,
+            "first edit"
+        );
+
+        let rsvp = manager
+            .update_if_version(rsvp.id, 2, changes)
+            .await
+            .unwrap();
+        assert_eq!(rsvp.note, "stale edit");
+        assert_eq!(rsvp.version, 3);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn update_if_version_should_check_new_window() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool).await;
+        manager
+            .add_blackout(
+                "1021".into(),
+                "2023-01-10T00:00:00-0700".parse().unwrap(),
+                "2023-01-11T00:00:00-0700".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let changes = ReservationChanges {
+            window: Some((
+                "2023-01-10T10:00:00-0700".parse().unwrap(),
+                "2023-01-10T12:00:00-0700".parse().unwrap(),
+            )),
+            ..Default::default()
+        };
+        let err = manager
+            .update_if_version(rsvp.id.clone(), 1, changes)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::ResourceUnavailable { .. }));
+        assert_eq!(manager.get(rsvp.id.clone()).await.unwrap().version, 1);
+
+        let changes = ReservationChanges {
+            window: Some((
+                "2023-01-12T10:00:00-0700".parse().unwrap(),
+                "2023-01-12T12:00:00-0700".parse().unwrap(),
+            )),
+            ..Default::default()
+        };
+        let rsvp = manager
+            .update_if_version(rsvp.id, 1, changes)
+            .await
+            .unwrap();
+        assert_eq!(rsvp.version, 2);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_from_group_should_skip_busy_members() {
+        let (manager, _) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "standard-1",
+            "2022-12-25T10:00:00Z",
+            "2022-12-25T12:00:00Z",
+            "",
+        )
+        .await;
+        manager
+            .add_to_group("standard".into(), "standard-1".into())
+            .await
+            .unwrap();
+        manager
+            .add_to_group("standard".into(), "standard-2".into())
+            .await
+            .unwrap();
+
+        let start: DateTime<Utc> = "2022-12-25T11:00:00Z".parse().unwrap();
+        let end: DateTime<Utc> = "2022-12-25T13:00:00Z".parse().unwrap();
+        let rsvp = manager
+            .reserve_from_group("tyrId".into(), "standard".into(), start, end, "".into())
+            .await
+            .unwrap();
+        assert_eq!(rsvp.resource_id, "standard-2");
+
+        let err = manager
+            .reserve_from_group("bobId".into(), "standard".into(), start, end, "".into())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+
+        let err = manager
+            .reserve_from_group("bobId".into(), "deluxe".into(), start, end, "".into())
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::NotFound);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn heatmap_should_count_every_hour_covered() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        manager.set_capacity("713".into(), 2).await.unwrap();
+        // 15:30 - 18:00 at -07:00, touching the 15, 16 and 17 o'clock cells of Sunday
+        make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-12-25T15:30:00-0700",
+            "2022-12-25T18:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "713",
+            "2022-12-25T17:00:00-0700",
+            "2022-12-25T17:30:00-0700",
+            "",
+        )
+        .await;
+
+        let start: DateTime<Utc> = "2022-12-25T00:00:00Z".parse().unwrap();
+        let end: DateTime<Utc> = "2022-12-27T00:00:00Z".parse().unwrap();
+        let tz = FixedOffset::west(7 * 3600);
+        let cells = manager
+            .heatmap("713".into(), start, end, tz, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            cells,
+            vec![
+                ((chrono::Weekday::Sun, 15), 1),
+                ((chrono::Weekday::Sun, 16), 1),
+                ((chrono::Weekday::Sun, 17), 2),
+            ]
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn heatmap_should_handle_far_future_windows() {
+        let (manager, _) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2300-01-01T10:30:00+0530",
+            "2300-01-01T11:30:00+0530",
+            "",
+        )
+        .await;
+
+        let start: DateTime<Utc> = "2300-01-01T00:00:00Z".parse().unwrap();
+        let end: DateTime<Utc> = "2300-01-02T00:00:00Z".parse().unwrap();
+        let tz = FixedOffset::east(5 * 3600 + 1800);
+        let cells = manager
+            .heatmap("713".into(), start, end, tz, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            cells,
+            vec![
+                ((chrono::Weekday::Mon, 10), 1),
+                ((chrono::Weekday::Mon, 11), 1),
+            ]
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn subscribe_resource_should_see_hold_expiry() {
+        let now: DateTime<Utc> = "2022-12-20T09:00:00Z".parse().unwrap();
+        let clock = crate::ManualClock::new(now);
+        let manager = ReservationManager::new(migrated_pool.clone()).with_clock(clock.clone());
+        let rsvp = |rid: &str| {
+            Reservation::new_pending(
+                "tyrId",
+                rid,
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-25T18:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+
+        let mut events = manager.subscribe_resource("1021".into());
+        let held = manager
+            .hold(rsvp("1021"), Duration::minutes(10))
+            .await
+            .unwrap();
+        manager
+            .hold(rsvp("1022"), Duration::minutes(5))
+            .await
+            .unwrap();
+        let wait = std::time::Duration::from_secs(1);
+        let event = tokio::time::timeout(wait, events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.kind, ReservationEventKind::Held);
+        assert_eq!(event.reservation.id, held.id);
+
+        clock.advance(Duration::minutes(11));
+        assert_eq!(manager.expire_holds().await.unwrap().len(), 2);
+
+        // the other resource's expiry is filtered out
+        let event = tokio::time::timeout(wait, events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.kind, ReservationEventKind::HoldExpired);
+        assert_eq!(event.reservation.id, held.id);
+        assert_eq!(event.reservation.resource_id, "1021");
+        assert!(tokio::time::timeout(wait, events.next()).await.is_err());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn search_ranked_should_order_by_relevance() {
+        let (manager, once) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-12-25T10:00:00-0700",
+            "2022-12-25T11:00:00-0700",
+            "projector needed, 12 seats",
+        )
+        .await;
+        let (_, twice) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "713",
+            "2022-12-25T12:00:00-0700",
+            "2022-12-25T13:00:00-0700",
+            "bring the projector, check the projector cable",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "bobId",
+            "713",
+            "2022-12-25T14:00:00-0700",
+            "2022-12-25T15:00:00-0700",
+            "whiteboard only",
+        )
+        .await;
+
+        let filter = ReservationQueryBuilder::default()
+            .resource_id("713")
+            .start(
+                "2022-12-25T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-26T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(abi::ReservationStatus::Pending as i32)
+            .build()
+            .unwrap();
+        let rsvps = manager
+            .search_ranked("projector".into(), filter.clone())
+            .await
+            .unwrap();
+        let ids: Vec<_> = rsvps.iter().map(|r| r.id.clone()).collect();
+        assert_eq!(ids, vec![twice.id, once.id.clone()]);
+
+        // "12" is too short to rank, it is matched as a substring
+        let rsvps = manager
+            .search_ranked("12".into(), filter.clone())
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].id, once.id);
+
+        // the rest of the filter applies too, the 25th is a Sunday
+        let filter = abi::ReservationQuery {
+            duration_class: DurationClass::Hourly as i32,
+            weekdays: vec![0],
+            ..filter
+        };
+        let rsvps = manager
+            .search_ranked("projector".into(), filter.clone())
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 2);
+        let filter = abi::ReservationQuery {
+            weekdays: vec![1],
+            ..filter
+        };
+        let rsvps = manager
+            .search_ranked("projector".into(), filter)
+            .await
+            .unwrap();
+        assert!(rsvps.is_empty());
+    }
+
+    #[derive(Debug, Default)]
+    struct FailingSink {
+        reserved: std::sync::atomic::AtomicUsize,
+        released: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ReservationSink for Arc<FailingSink> {
+        async fn reserve_external(&self, _rsvp: &abi::Reservation) -> Result<(), abi::Error> {
+            self.reserved
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Err(abi::Error::Unknown)
+        }
+
+        async fn release_external(&self, _rsvp: &abi::Reservation) -> Result<(), abi::Error> {
+            self.released
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_should_roll_back_when_sink_fails() {
+        let sink = Arc::new(FailingSink::default());
+        let manager = ReservationManager::new(migrated_pool.clone()).with_sink(sink.clone());
+        let rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+
+        let err = manager.reserve(rsvp).await.unwrap_err();
+        assert_eq!(err, abi::Error::Unknown);
+        assert_eq!(sink.reserved.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(sink.released.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        let count: i64 = sqlx::query_scalar("SELECT count(*) FROM rsvp.reservations")
+            .fetch_one(&migrated_pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn at_instant_should_treat_end_as_exclusive() {
+        let (manager, _ended) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-12-25T10:00:00Z",
+            "2022-12-25T12:00:00Z",
+            "",
+        )
+        .await;
+        let (_, ongoing) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "714",
+            "2022-12-25T11:00:00Z",
+            "2022-12-25T13:00:00Z",
+            "",
+        )
+        .await;
+
+        let t: DateTime<Utc> = "2022-12-25T12:00:00Z".parse().unwrap();
+        let rsvps = manager.at_instant(None, t).await.unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].id, ongoing.id);
+
+        assert!(manager.at_instant(Some("713"), t).await.unwrap().is_empty());
+        let t: DateTime<Utc> = "2022-12-25T11:59:59Z".parse().unwrap();
+        assert_eq!(manager.at_instant(Some("713"), t).await.unwrap().len(), 1);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_with_shift_should_nudge_within_tolerance() {
+        let (manager, _) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "713",
+            "2022-12-25T09:00:00Z",
+            "2022-12-25T10:10:00Z",
+            "",
+        )
+        .await;
+        let rsvp = Reservation::new_pending(
+            "tyrId",
+            "713",
+            "2022-12-25T10:00:00Z".parse().unwrap(),
+            "2022-12-25T11:00:00Z".parse().unwrap(),
+            "",
+        );
+
+        let err = manager
+            .reserve_with_shift(rsvp.clone(), Some(Duration::minutes(5)))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+
+        let (booked, shift) = manager
+            .reserve_with_shift(rsvp, Some(Duration::minutes(15)))
+            .await
+            .unwrap();
+        assert_eq!(shift, Duration::minutes(10));
+        let ts = |s: &str| abi::convert_to_timestamp(s.parse().unwrap());
+        assert_eq!(booked.start_time, Some(ts("2022-12-25T10:10:00Z")));
+        assert_eq!(booked.end_time, Some(ts("2022-12-25T11:10:00Z")));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn pending_starting_soon_should_skip_confirmed() {
+        let (manager, pending) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "1021",
+            "2022-12-25T10:00:00Z",
+            "2022-12-25T11:00:00Z",
+            "",
+        )
+        .await;
+        let (_, confirmed) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "1022",
+            "2022-12-25T10:00:00Z",
+            "2022-12-25T11:00:00Z",
+            "",
+        )
+        .await;
+        manager.change_status(confirmed.id, "").await.unwrap();
+        make_reservation(
+            &migrated_pool,
+            "bobId",
+            "1023",
+            "2022-12-25T12:00:00Z",
+            "2022-12-25T13:00:00Z",
+            "",
+        )
+        .await;
+
+        let now: DateTime<Utc> = "2022-12-25T08:00:00Z".parse().unwrap();
+        let rsvps = manager
+            .pending_starting_soon(Duration::hours(3), now)
+            .await
+            .unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].id, pending.id);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn cancellable_should_stop_slow_query() {
+        let mut conn = migrated_pool.acquire().await.unwrap();
+        let cancel = CancellationToken::new();
+        let pid = backend_pid(&mut conn, Some(&cancel)).await.unwrap();
+        assert!(pid.is_some());
+
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            trigger.cancel();
+        });
+        let started = std::time::Instant::now();
+        let ret = cancellable(
+            &migrated_pool,
+            pid,
+            Some(&cancel),
+            sqlx::query("SELECT pg_sleep(30)").execute(&mut conn),
+        )
+        .await;
+        assert_eq!(ret.unwrap_err(), abi::Error::Cancelled);
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+
+        // the connection is usable again
+        let one: i32 = sqlx::query_scalar("SELECT 1")
+            .fetch_one(&mut conn)
+            .await
+            .unwrap();
+        assert_eq!(one, 1);
+        drop(conn);
+
+        // an already cancelled token stops the public reads too
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let start: DateTime<Utc> = "2022-12-25T00:00:00Z".parse().unwrap();
+        let err = manager
+            .availability_for("713".into(), start, start + Duration::days(1), Some(cancel))
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::Cancelled);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn user_sessions_should_merge_back_to_back_bookings() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        for (rid, start, end) in [
+            ("713", "2022-12-25T09:00:00Z", "2022-12-25T10:00:00Z"),
+            ("714", "2022-12-25T10:00:00Z", "2022-12-25T11:00:00Z"),
+            // a short walk between rooms
+            ("715", "2022-12-25T11:03:00Z", "2022-12-25T12:00:00Z"),
+            ("713", "2022-12-25T14:00:00Z", "2022-12-25T15:00:00Z"),
+        ] {
+            make_reservation(&migrated_pool, "tyrId", rid, start, end, "").await;
+        }
+
+        let t = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let sessions = manager
+            .user_sessions(
+                "tyrId".into(),
+                t("2022-12-25T00:00:00Z"),
+                t("2022-12-26T00:00:00Z"),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            sessions,
+            vec![
+                (t("2022-12-25T09:00:00Z"), t("2022-12-25T12:00:00Z")),
+                (t("2022-12-25T14:00:00Z"), t("2022-12-25T15:00:00Z")),
+            ]
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn verify_constraints_should_report_missing_constraint() {
+        let manager = ReservationManager::new(migrated_pool.clone()).with_strict_constraints(true);
+        manager.verify_constraints().await.unwrap();
+
+        sqlx::query("ALTER TABLE rsvp.reservations DROP CONSTRAINT reservations_conflict")
+            .execute(&migrated_pool)
+            .await
+            .unwrap();
+        let missing = abi::Error::MissingConstraint("reservations_conflict".into());
+        assert_eq!(manager.verify_constraints().await.unwrap_err(), missing);
+
+        // strict mode refuses to book without it
+        let rsvp = Reservation::new_pending(
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-28T12:00:00-0700".parse().unwrap(),
+            "",
+        );
+        assert_eq!(manager.reserve(rsvp).await.unwrap_err(), missing);
+
+        manager.restore_conflict_constraint().await.unwrap();
+        manager.verify_constraints().await.unwrap();
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn sync_diff_should_bucket_changes() {
+        let (manager, unchanged) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-12-25T09:00:00Z",
+            "2022-12-25T10:00:00Z",
+            "",
+        )
+        .await;
+        let (_, edited) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "714",
+            "2022-12-25T09:00:00Z",
+            "2022-12-25T10:00:00Z",
+            "",
+        )
+        .await;
+        let (_, deleted) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "715",
+            "2022-12-25T09:00:00Z",
+            "2022-12-25T10:00:00Z",
+            "",
+        )
+        .await;
+        let known = vec![
+            (unchanged.id.clone(), unchanged.version),
+            (edited.id.clone(), edited.version),
+            (deleted.id.clone(), deleted.version),
+        ];
+
+        let (_, added) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "716",
+            "2022-12-25T09:00:00Z",
+            "2022-12-25T10:00:00Z",
+            "",
+        )
+        .await;
+        manager
+            .update_note(edited.id.clone(), "moved chairs".into())
+            .await
+            .unwrap();
+        manager.delete(deleted.id.clone()).await.unwrap();
+
+        let filter = ReservationQueryBuilder::default()
+            .user_id("tyrId")
+            .start(
+                "2022-12-25T00:00:00Z"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-26T00:00:00Z"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(abi::ReservationStatus::Pending as i32)
+            .build()
+            .unwrap();
+        let diff = manager.sync_diff(known, filter).await.unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].id, added.id);
+        assert_eq!(diff.updated.len(), 1);
+        assert_eq!(diff.updated[0].id, edited.id);
+        assert_eq!(diff.updated[0].note, "moved chairs");
+        assert_eq!(diff.removed, vec![deleted.id]);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn sync_diff_should_apply_every_filter() {
+        let (manager, standup) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "716",
+            "2022-12-25T09:00:00Z",
+            "2022-12-25T10:00:00Z",
+            "standup",
+        )
+        .await;
+        let (_, retro) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "716",
+            "2022-12-25T11:00:00Z",
+            "2022-12-25T12:00:00Z",
+            "retro",
+        )
+        .await;
+        let known = vec![(standup.id.clone(), standup.version)];
+
+        let filter = ReservationQueryBuilder::default()
+            .user_id("tyrId")
+            .start(
+                "2022-12-25T00:00:00Z"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-26T00:00:00Z"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(abi::ReservationStatus::Pending as i32)
+            .search("retro")
+            .build()
+            .unwrap();
+        let diff = manager.sync_diff(known, filter).await.unwrap();
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].id, retro.id);
+        assert!(diff.updated.is_empty());
+        // no longer in the filtered view
+        assert_eq!(diff.removed, vec![standup.id]);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_should_filter_by_min_party_size() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let rsvp = |rid: &str, party_size: Option<i32>| Reservation {
+            party_size,
+            special_requests: vec!["late check-in".into()],
+            ..Reservation::new_pending(
+                "tyrId",
+                rid,
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-25T18:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+        let large = manager.reserve(rsvp("713", Some(6))).await.unwrap();
+        manager.reserve(rsvp("714", Some(2))).await.unwrap();
+        manager.reserve(rsvp("715", None)).await.unwrap();
+
+        let stored = manager.get(large.id.clone()).await.unwrap();
+        assert_eq!(stored.party_size, Some(6));
+        assert_eq!(stored.special_requests, vec!["late check-in".to_string()]);
+
+        let query = ReservationQueryBuilder::default()
+            .start(
+                "2022-12-25T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-26T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(abi::ReservationStatus::Pending as i32)
+            .min_party_size(4)
+            .build()
+            .unwrap();
+        let rsvps = manager.query(query).await.unwrap();
+        assert_eq!(rsvps.len(), 1);
+        assert_eq!(rsvps[0].id, large.id);
+
+        let err = manager.reserve(rsvp("716", Some(0))).await.unwrap_err();
+        assert_eq!(err, abi::Error::InvalidPartySize(0));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn longest_per_resource_should_pick_one_per_resource() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let mut ids = vec![];
+        for (rid, start, end) in [
+            ("713", "2022-12-25T09:00:00Z", "2022-12-25T10:00:00Z"),
+            ("713", "2022-12-25T11:00:00Z", "2022-12-25T14:00:00Z"),
+            ("714", "2022-12-25T09:00:00Z", "2022-12-25T11:30:00Z"),
+            ("714", "2022-12-25T12:00:00Z", "2022-12-25T13:00:00Z"),
+        ] {
+            let (_, rsvp) = make_reservation(&migrated_pool, "tyrId", rid, start, end, "").await;
+            ids.push(rsvp.id);
+        }
+
+        let longest = manager.longest_per_resource(None).await.unwrap();
+        assert_eq!(longest.len(), 2);
+        assert_eq!(longest["713"].id, ids[1]);
+        assert_eq!(longest["714"].id, ids[2]);
+
+        let t = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let window = (t("2022-12-25T08:00:00Z"), t("2022-12-25T11:00:00Z"));
+        let longest = manager.longest_per_resource(Some(window)).await.unwrap();
+        assert_eq!(longest["713"].id, ids[0]);
+        assert!(!longest.contains_key("714"));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn approve_should_only_accept_assigned_approver() {
+        let manager = ReservationManager::new(migrated_pool.clone()).with_auto_confirm(true);
+        let rsvp = Reservation {
+            approver_id: "managerId".into(),
+            ..Reservation::new_pending(
+                "tyrId",
+                "1021",
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-25T18:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+        let rsvp = manager.reserve(rsvp).await.unwrap();
+        assert_eq!(rsvp.status, abi::ReservationStatus::Pending as i32);
+        assert_eq!(rsvp.approval_status, ApprovalStatus::Pending as i32);
+
+        let err = manager.approve(rsvp.id.clone(), "tyrId").await.unwrap_err();
+        assert_eq!(err, abi::Error::NotAuthorized);
+        let err = manager
+            .change_status(rsvp.id.clone(), "tyrId")
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::NotAuthorized);
+
+        let rsvp = manager.approve(rsvp.id, "managerId").await.unwrap();
+        assert_eq!(rsvp.status, abi::ReservationStatus::Confirmed as i32);
+        assert_eq!(rsvp.approval_status, ApprovalStatus::Approved as i32);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn monthly_counts_should_bucket_by_local_start_month() {
+        let (manager, _) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-03-10T10:00:00Z",
+            "2022-03-10T11:00:00Z",
+            "",
+        )
+        .await;
+        // starts on march 31st in local time, ends in april
+        make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "713",
+            "2022-03-31T23:00:00-0700",
+            "2022-04-01T02:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "713",
+            "2022-07-01T10:00:00Z",
+            "2022-07-01T11:00:00Z",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "714",
+            "2022-07-01T10:00:00Z",
+            "2022-07-01T11:00:00Z",
+            "",
+        )
+        .await;
+
+        let tz = FixedOffset::west(7 * 3600);
+        let counts = manager.monthly_counts(Some("713"), 2022, tz).await.unwrap();
+        let mut expected = [0; 12];
+        expected[2] = 2;
+        expected[6] = 1;
+        assert_eq!(counts, expected);
+
+        let counts = manager.monthly_counts(None, 2022, tz).await.unwrap();
+        assert_eq!(counts[6], 2);
+        let counts = manager.monthly_counts(None, 2021, tz).await.unwrap();
+        assert_eq!(counts, [0; 12]);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_exclusive_should_block_both_sides() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        let rsvp = abi::Reservation::new_pending(
+            "tyrId",
+            "ocean-view-room-713",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-25T17:00:00-0700".parse().unwrap(),
+            "premium",
+        );
+        let (main, buffers) = manager
+            .reserve_exclusive(rsvp, Duration::minutes(30))
+            .await
+            .unwrap();
+        assert_eq!(main.note, "premium");
+        assert_eq!(buffers.len(), 2);
+        assert!(buffers
+            .iter()
+            .all(|b| b.status == abi::ReservationStatus::Blocked as i32));
+        assert_eq!(buffers[0].end_time, main.start_time);
+        assert_eq!(buffers[1].start_time, main.end_time);
+
+        let rows: i64 = sqlx::query_scalar("SELECT count(*) FROM rsvp.reservations")
+            .fetch_one(&migrated_pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 3);
+
+        // right after the booking, inside the trailing buffer
+        let adjacent = abi::Reservation::new_pending(
+            "aliceId",
+            "ocean-view-room-713",
+            "2022-12-25T17:00:00-0700".parse().unwrap(),
+            "2022-12-25T18:00:00-0700".parse().unwrap(),
+            "",
+        );
+        let err = manager.reserve(adjacent).await.unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_exclusive_conflicting_buffer_should_roll_back() {
+        let (manager, _) = make_reservation(
+            &migrated_pool,
+            "aliceId",
+            "ocean-view-room-713",
+            "2022-12-25T17:15:00-0700",
+            "2022-12-25T18:00:00-0700",
+            "",
+        )
+        .await;
+        let rsvp = abi::Reservation::new_pending(
+            "tyrId",
+            "ocean-view-room-713",
+            "2022-12-25T15:00:00-0700".parse().unwrap(),
+            "2022-12-25T17:00:00-0700".parse().unwrap(),
+            "premium",
+        );
+        let err = manager
+            .reserve_exclusive(rsvp, Duration::minutes(30))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::ConflictReservation(_)));
+
+        let rows: i64 = sqlx::query_scalar("SELECT count(*) FROM rsvp.reservations")
+            .fetch_one(&migrated_pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 1);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_with_resource_should_attach_catalog_entries() {
+        let (manager, _) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "ocean-view-room-713",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-25T17:00:00-0700",
+            "",
+        )
+        .await;
+        make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "ocean-view-room-714",
+            "2022-12-26T15:00:00-0700",
+            "2022-12-26T17:00:00-0700",
+            "",
+        )
+        .await;
+        manager
+            .set_display_name("ocean-view-room-713".into(), "Ocean View 713".into())
+            .await
+            .unwrap();
+
+        let query = ReservationQueryBuilder::default()
+            .user_id("tyrId")
+            .start(
+                "2022-12-25T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-27T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .build()
+            .unwrap();
+        let rows = manager.query_with_resource(query).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        let named = rows
+            .iter()
+            .find(|(r, _)| r.resource_id == "ocean-view-room-713")
+            .unwrap();
+        assert_eq!(named.1.display_name.as_deref(), Some("Ocean View 713"));
+        assert_eq!(named.1.capacity, 1);
+        let unnamed = rows
+            .iter()
+            .find(|(r, _)| r.resource_id == "ocean-view-room-714")
+            .unwrap();
+        assert_eq!(unnamed.1, ResourceInfo::unregistered("ocean-view-room-714"));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn reserve_units_should_respect_capacity_in_units() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        manager.set_capacity("bus-42".into(), 10).await.unwrap();
+        let seats = |units: i32, start: &str, end: &str| abi::Reservation {
+            units: Some(units),
+            ..abi::Reservation::new_pending(
+                "tyrId",
+                "bus-42",
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            )
+        };
+
+        manager
+            .reserve(seats(
+                6,
+                "2022-12-25T15:00:00-0700",
+                "2022-12-25T17:00:00-0700",
+            ))
+            .await
+            .unwrap();
+        let err = manager
+            .reserve(seats(
+                5,
+                "2022-12-25T16:00:00-0700",
+                "2022-12-25T18:00:00-0700",
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::CapacityExceeded {
+                resource_id: "bus-42".into(),
+                capacity: 10,
+            }
+        );
+        let rsvp = manager
+            .reserve(seats(
+                4,
+                "2022-12-25T16:00:00-0700",
+                "2022-12-25T18:00:00-0700",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(manager.get(rsvp.id).await.unwrap().units, Some(4));
+
+        let slots = manager
+            .availability_for(
+                "bus-42".into(),
+                "2022-12-25T16:00:00-0700".parse().unwrap(),
+                "2022-12-25T17:00:00-0700".parse().unwrap(),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(slots[0].remaining(), 0);
+
+        // more units than the resource has at all
+        let err = manager
+            .reserve(seats(
+                11,
+                "2022-12-26T16:00:00-0700",
+                "2022-12-26T18:00:00-0700",
+            ))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, abi::Error::CapacityExceeded { .. }));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn short_gaps_should_report_slivers_between_bookings() {
+        let manager = ReservationManager::new(migrated_pool.clone()).with_auto_confirm(true);
+        for (start, end) in [
+            ("2022-12-25T09:00:00Z", "2022-12-25T10:00:00Z"),
+            // 10 minutes after the first
+            ("2022-12-25T10:10:00Z", "2022-12-25T11:00:00Z"),
+            // an hour after the second
+            ("2022-12-25T12:00:00Z", "2022-12-25T13:00:00Z"),
+        ] {
+            let rsvp = abi::Reservation::new_pending(
+                "tyrId",
+                "713",
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            );
+            manager.reserve(rsvp).await.unwrap();
+        }
+
+        let window = (
+            "2022-12-25T00:00:00Z".parse().unwrap(),
+            "2022-12-26T00:00:00Z".parse().unwrap(),
+        );
+        let gaps = manager
+            .short_gaps("713".into(), window, Duration::minutes(15))
+            .await
+            .unwrap();
+        assert_eq!(
+            gaps,
+            vec![(
+                "2022-12-25T10:00:00Z".parse().unwrap(),
+                "2022-12-25T10:10:00Z".parse().unwrap()
+            )]
+        );
+
+        let gaps = manager
+            .short_gaps("713".into(), window, Duration::minutes(5))
+            .await
+            .unwrap();
+        assert!(gaps.is_empty());
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn receipt_should_show_local_times_and_duration() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        manager
+            .set_display_name("ocean-view-room-713".into(), "Ocean View 713".into())
+            .await
+            .unwrap();
+        let rsvp = abi::Reservation {
+            amount_cents: Some(25000),
+            currency: Some("USD".into()),
+            ..abi::Reservation::new_pending(
+                "tyrId",
+                "ocean-view-room-713",
+                "2022-12-25T22:00:00Z".parse().unwrap(),
+                "2022-12-26T00:30:00Z".parse().unwrap(),
+                "",
+            )
+        };
+        let rsvp = manager.reserve(rsvp).await.unwrap();
+
+        let tz = FixedOffset::west(7 * 3600);
+        let receipt = manager.receipt(rsvp.id.clone(), tz).await.unwrap();
+        assert_eq!(receipt.id, rsvp.id);
+        assert_eq!(receipt.reference, rsvp.reference);
+        assert_eq!(receipt.resource_name, "Ocean View 713");
+        assert_eq!(receipt.start.to_rfc3339(), "2022-12-25T15:00:00-07:00");
+        assert_eq!(receipt.end.to_rfc3339(), "2022-12-25T17:30:00-07:00");
+        assert_eq!(receipt.duration, Duration::minutes(150));
+        assert_eq!(receipt.status, ReservationStatus::Pending);
+        assert_eq!(receipt.amount_cents, Some(25000));
+        assert_eq!(receipt.currency.as_deref(), Some("USD"));
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn cross_tenant_overlaps_should_report_shared_resource() {
+        let (_, rsvp) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "ocean-view-room-713",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-25T17:00:00-0700",
+            "",
+        )
+        .await;
+        sqlx::query("CREATE SCHEMA tenant_b")
+            .execute(&migrated_pool)
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE tenant_b.reservations (LIKE rsvp.reservations INCLUDING ALL)")
+            .execute(&migrated_pool)
+            .await
+            .unwrap();
+        for (rid, start, end) in [
+            (
+                "ocean-view-room-713",
+                "2022-12-25T23:00:00Z",
+                "2022-12-26T01:00:00Z",
+            ),
+            // same window, different resource
+            (
+                "ocean-view-room-714",
+                "2022-12-25T22:00:00Z",
+                "2022-12-26T00:00:00Z",
+            ),
+        ] {
+            sqlx::query(
+                "INSERT INTO tenant_b.reservations (user_id, resource_id, timespan, note) VALUES ('aliceId', $1, $2, '')",
+            )
+            .bind(rid)
+            .bind(PgRange::from(
+                start.parse::<DateTime<Utc>>().unwrap()..end.parse::<DateTime<Utc>>().unwrap(),
+            ))
+            .execute(&migrated_pool)
+            .await
+            .unwrap();
+        }
+
+        let single = ReservationManager::new(migrated_pool.clone()).with_tenant_schemas(["rsvp"]);
+        assert!(single.cross_tenant_overlaps().await.unwrap().is_empty());
+
+        let manager = ReservationManager::new(migrated_pool.clone())
+            .with_tenant_schemas(["rsvp", "tenant_b"]);
+        let overlaps = manager.cross_tenant_overlaps().await.unwrap();
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(overlaps[0].0.id, rsvp.id);
+        assert_eq!(overlaps[0].1.user_id, "aliceId");
+        assert_eq!(overlaps[0].1.resource_id, "ocean-view-room-713");
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn query_for_viewer_should_redact_others_reservations() {
+        let (manager, mine) = make_reservation(
+            &migrated_pool,
+            "tyrId",
+            "ocean-view-room-713",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-25T17:00:00-0700",
+            "my note",
+        )
+        .await;
+        let theirs = Reservation {
+            created_by: "agentId".into(),
+            approver_id: "managerId".into(),
+            ..Reservation::new_pending(
+                "aliceId",
+                "ocean-view-room-713",
+                "2022-12-26T15:00:00-0700".parse().unwrap(),
+                "2022-12-26T17:00:00-0700".parse().unwrap(),
+                "alice's note",
+            )
+        };
+        manager.reserve(theirs).await.unwrap();
+
+        let query = ReservationQueryBuilder::default()
+            .resource_id("ocean-view-room-713")
+            .start(
+                "2022-12-25T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .end(
+                "2022-12-27T00:00:00-0700"
+                    .parse::<prost_types::Timestamp>()
+                    .unwrap(),
+            )
+            .status(ReservationStatus::Pending)
+            .build()
+            .unwrap();
+        let rows = manager
+            .query_for_viewer(query, "tyrId".into())
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        let own = rows.iter().find(|r| r.is_mine).unwrap();
+        assert_eq!(own.reservation.id, mine.id);
+        assert_eq!(own.reservation.user_id, "tyrId");
+        assert_eq!(own.reservation.note, "my note");
+        let foreign = rows.iter().find(|r| !r.is_mine).unwrap();
+        assert_eq!(foreign.reservation.user_id, "");
+        assert_eq!(foreign.reservation.note, "");
+        assert_eq!(foreign.reservation.created_by, "");
+        assert_eq!(foreign.reservation.approver_id, "");
+        assert_eq!(foreign.reservation.resource_id, "ocean-view-room-713");
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn nearest_available_should_order_by_distance() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        // roughly 1km and 5km north of the center, and one far away
+        let center = (37.7749, -122.4194);
+        for (rid, lat, lon) in [
+            ("court-far", 37.8199, -122.4194),
+            ("court-near", 37.7839, -122.4194),
+            ("court-other-city", 34.0522, -118.2437),
+        ] {
+            manager.set_location(rid.into(), lat, lon).await.unwrap();
+        }
+        let rsvp = abi::Reservation::new_pending(
+            "tyrId",
+            "court-near",
+            "2022-12-25T09:00:00Z".parse().unwrap(),
+            "2022-12-25T10:00:00Z".parse().unwrap(),
+            "",
+        );
+        manager.reserve(rsvp).await.unwrap();
+
+        let window = (
+            "2022-12-25T09:00:00Z".parse().unwrap(),
+            "2022-12-25T18:00:00Z".parse().unwrap(),
+        );
+        let found = manager
+            .nearest_available(center, 10.0, window, Duration::hours(1))
+            .await
+            .unwrap();
+        let found: Vec<_> = found
+            .iter()
+            .map(|(info, start)| (info.id.as_str(), start.to_rfc3339()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("court-near", "2022-12-25T10:00:00+00:00".to_string()),
+                ("court-far", "2022-12-25T09:00:00+00:00".to_string()),
+            ]
+        );
+
+        let err = manager
+            .set_location("court-near".into(), 91.0, 0.0)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err,
+            abi::Error::InvalidLocation {
+                lat: 91.0,
+                lon: 0.0
+            }
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn nearest_available_should_skip_blackouts() {
+        let manager = ReservationManager::new(migrated_pool.clone());
+        manager
+            .set_location("court-near".into(), 37.7839, -122.4194)
+            .await
+            .unwrap();
+        manager
+            .add_blackout(
+                "court-near".into(),
+                "2022-12-25T08:00:00Z".parse().unwrap(),
+                "2022-12-25T11:30:00Z".parse().unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let window = (
+            "2022-12-25T09:00:00Z".parse().unwrap(),
+            "2022-12-25T18:00:00Z".parse().unwrap(),
+        );
+        let found = manager
+            .nearest_available((37.7749, -122.4194), 10.0, window, Duration::hours(1))
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            found[0].1,
+            "2022-12-25T11:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn cancellation_rate_should_divide_cancelled_by_decided() {
+        let manager = ReservationManager::new(migrated_pool.clone()).with_auto_confirm(true);
+        let window = (
+            "2022-12-25T00:00:00Z".parse().unwrap(),
+            "2022-12-26T00:00:00Z".parse().unwrap(),
+        );
+        assert_eq!(manager.cancellation_rate(None, window).await.unwrap(), 0.0);
+
+        let mut ids = vec![];
+        for hour in 9..13 {
+            let rsvp = abi::Reservation::new_pending(
+                "tyrId",
+                "713",
+                format!("2022-12-25T{hour}:00:00Z").parse().unwrap(),
+                format!("2022-12-25T{hour}:30:00Z").parse().unwrap(),
+                "",
+            );
+            ids.push(manager.reserve(rsvp).await.unwrap().id);
+        }
+        manager.cancel(ids[0].clone()).await.unwrap();
+
+        assert_eq!(
+            manager
+                .cancellation_rate(Some("713"), window)
+                .await
+                .unwrap(),
+            0.25
+        );
+        assert_eq!(
+            manager
+                .cancellation_rate(Some("714"), window)
+                .await
+                .unwrap(),
+            0.0
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn cancellation_rate_should_count_by_timespan_not_cancel_time() {
+        let t = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let clock = crate::ManualClock::new(t("2022-12-20T00:00:00Z"));
+        let manager = ReservationManager::new(migrated_pool.clone())
+            .with_clock(clock.clone())
+            .with_auto_confirm(true);
+        let window = (t("2022-12-25T00:00:00Z"), t("2022-12-26T00:00:00Z"));
+        let reserve = |start: &str, end: &str| {
+            let rsvp = abi::Reservation::new_pending(
+                "tyrId",
+                "713",
+                start.parse().unwrap(),
+                end.parse().unwrap(),
+                "",
+            );
+            manager.reserve(rsvp)
+        };
+        let inside = reserve("2022-12-25T09:00:00Z", "2022-12-25T10:00:00Z")
+            .await
+            .unwrap();
+        reserve("2022-12-25T11:00:00Z", "2022-12-25T12:00:00Z")
+            .await
+            .unwrap();
+        let outside = reserve("2022-12-28T09:00:00Z", "2022-12-28T10:00:00Z")
+            .await
+            .unwrap();
+
+        // cancelled during the window, booked for after it
+        clock.set(t("2022-12-25T12:00:00Z"));
+        manager.cancel(outside.id).await.unwrap();
+        // booked during the window, cancelled after it
+        clock.set(t("2022-12-27T00:00:00Z"));
+        manager.cancel(inside.id.clone()).await.unwrap();
+
+        let cancelled_at: DateTime<Utc> =
+            sqlx::query_scalar("SELECT cancelled_at FROM rsvp.reservations WHERE id = $1")
+                .bind(Uuid::parse_str(&inside.id).unwrap())
+                .fetch_one(&migrated_pool)
+                .await
+                .unwrap();
+        assert_eq!(cancelled_at, t("2022-12-27T00:00:00Z"));
+        assert_eq!(
+            manager
+                .cancellation_rate(Some("713"), window)
+                .await
+                .unwrap(),
+            0.5
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn split_should_create_two_contiguous_reservations() {
+        let (manager, rsvp) = make_tyr_reservation(&migrated_pool).await;
+        let start = abi::to_datetime(rsvp.start_time.as_ref()).unwrap();
+        let end = abi::to_datetime(rsvp.end_time.as_ref()).unwrap();
+        let at = start + (end - start) / 2;
+
+        let err = manager.split(rsvp.id.clone(), end).await.unwrap_err();
+        assert_eq!(err, abi::Error::InvalidTime);
+
+        let (head, tail) = manager.split(rsvp.id.clone(), at).await.unwrap();
+        assert_eq!(head.id, rsvp.id);
+        assert_ne!(tail.id, rsvp.id);
+        assert_eq!(head.end_time, tail.start_time);
+        assert_eq!(head.start_time, rsvp.start_time);
+        assert_eq!(tail.end_time, rsvp.end_time);
+        let length = |r: &abi::Reservation| {
+            abi::to_datetime(r.end_time.as_ref()).unwrap()
+                - abi::to_datetime(r.start_time.as_ref()).unwrap()
+        };
+        assert_eq!(length(&head) + length(&tail), end - start);
+        assert_eq!(
+            (&tail.user_id, &tail.resource_id, tail.status, &tail.note),
+            (&rsvp.user_id, &rsvp.resource_id, rsvp.status, &rsvp.note)
+        );
+        assert_eq!(
+            manager.get(head.id).await.unwrap().end_time,
+            tail.start_time
+        );
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn split_should_keep_pending_approval() {
+        let manager = ReservationManager::new(migrated_pool.clone()).with_auto_confirm(true);
+        let rsvp = Reservation {
+            approver_id: "managerId".into(),
+            ..Reservation::new_pending(
+                "tyrId",
+                "1021",
+                "2022-12-25T15:00:00-0700".parse().unwrap(),
+                "2022-12-25T18:00:00-0700".parse().unwrap(),
+                "",
+            )
+        };
+        let rsvp = manager.reserve(rsvp).await.unwrap();
+
+        let at = "2022-12-25T16:00:00-0700".parse().unwrap();
+        let (_, tail) = manager.split(rsvp.id.clone(), at).await.unwrap();
+        assert_eq!(tail.status, abi::ReservationStatus::Pending as i32);
+        assert_eq!(tail.approver_id, "managerId");
+        assert_eq!(tail.approval_status, ApprovalStatus::Pending as i32);
+
+        let tail = manager.approve(tail.id, "managerId").await.unwrap();
+        assert_eq!(tail.status, abi::ReservationStatus::Confirmed as i32);
+    }
+
+    #[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+    async fn slot_bitmap_should_mark_overlapped_slots() {
+        let manager = ReservationManager::new(migrated_pool.clone()).with_auto_confirm(true);
+        // local 05:00 to 06:45, i.e. half-hour slots 10 to 13
+        let rsvp = abi::Reservation::new_pending(
+            "tyrId",
+            "713",
+            "2022-12-25T05:00:00-0700".parse().unwrap(),
+            "2022-12-25T06:45:00-0700".parse().unwrap(),
+            "",
+        );
+        manager.reserve(rsvp).await.unwrap();
+
+        let tz = FixedOffset::west(7 * 3600);
+        let date = NaiveDate::from_ymd(2022, 12, 25);
+        let bitmap = manager
+            .slot_bitmap("713".into(), date, 30, tz)
+            .await
+            .unwrap();
+        assert_eq!(bitmap.len(), 48);
+        let busy: Vec<usize> = (0..48).filter(|&i| bitmap[i]).collect();
+        assert_eq!(busy, vec![10, 11, 12, 13]);
+
+        let err = manager
+            .slot_bitmap("713".into(), date, 7, tz)
+            .await
+            .unwrap_err();
+        assert_eq!(err, abi::Error::InvalidSlotLength(7));
+    }
+
+    async fn make_tyr_reservation(pool: &PgPool) -> (ReservationManager, Reservation) {
+        make_reservation(
+            pool,
+            "tyrId",
+            "1021",
+            "2022-12-25T15:00:00-0700",
+            "2022-12-28T12:00:00-0700",
+            "hahaha...",
+        )
+        .await
+    }
+
+    async fn make_alice_reservation(pool: &PgPool) -> (ReservationManager, Reservation) {
+        make_reservation(
+            pool,
+            "aliceId",
+            "1021",
+            "2023-01-25T15:00:00-0700",
+            "2023-02-25T12:00:00-0700",
+            "hello...",
+        )
+        .await
+    }
+
+    async fn make_reservation(
+        pool: &PgPool,
+        uid: &str,
+        rid: &str,
+        start: &str,
+        end: &str,
+        note: &str,
+    ) -> (ReservationManager, Reservation) {
+        let manager = ReservationManager::new(pool.clone());
+        let rsvp =
+            Reservation::new_pending(uid, rid, start.parse().unwrap(), end.parse().unwrap(), note);
         let rsvp = manager.reserve(rsvp).await.unwrap();
 
         (manager, rsvp)
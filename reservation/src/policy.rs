@@ -0,0 +1,92 @@
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+use chrono::Duration;
+
+use crate::{Clock, ResourceId, SystemClock};
+
+/// a custom rule every reservation must pass before it is inserted
+pub trait ReservationPolicy: Debug + Send + Sync {
+    fn check(&self, rsvp: &abi::Reservation) -> Result<(), abi::Error>;
+}
+
+/// require reservations to start at least a minimum lead time from now,
+/// optionally overridden per resource
+#[derive(Debug, Clone)]
+pub struct LeadTimePolicy {
+    clock: Arc<dyn Clock>,
+    min_lead_time: Option<Duration>,
+    resource_min_lead_time: HashMap<ResourceId, Duration>,
+}
+
+impl Default for LeadTimePolicy {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemClock))
+    }
+}
+
+impl LeadTimePolicy {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            min_lead_time: None,
+            resource_min_lead_time: HashMap::new(),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_min_lead_time(mut self, lead_time: Duration) -> Self {
+        self.min_lead_time = Some(lead_time);
+        self
+    }
+
+    pub fn with_resource_min_lead_time(
+        mut self,
+        resource_id: impl Into<ResourceId>,
+        lead_time: Duration,
+    ) -> Self {
+        self.resource_min_lead_time
+            .insert(resource_id.into(), lead_time);
+        self
+    }
+}
+
+impl ReservationPolicy for LeadTimePolicy {
+    fn check(&self, rsvp: &abi::Reservation) -> Result<(), abi::Error> {
+        let lead_time = self
+            .resource_min_lead_time
+            .get(&rsvp.resource_id)
+            .or(self.min_lead_time.as_ref());
+
+        if let Some(lead_time) = lead_time {
+            let earliest = self.clock.now() + *lead_time;
+            if abi::to_datetime(rsvp.start_time.as_ref())? < earliest {
+                return Err(abi::Error::InsufficientLeadTime { earliest });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// reject reservations longer than the given duration
+#[derive(Debug, Clone, Copy)]
+pub struct MaxDurationPolicy(pub Duration);
+
+impl ReservationPolicy for MaxDurationPolicy {
+    fn check(&self, rsvp: &abi::Reservation) -> Result<(), abi::Error> {
+        let start = abi::to_datetime(rsvp.start_time.as_ref())?;
+        let end = abi::to_datetime(rsvp.end_time.as_ref())?;
+        if end - start > self.0 {
+            return Err(abi::Error::PolicyViolation(format!(
+                "reservation is longer than {} minutes",
+                self.0.num_minutes()
+            )));
+        }
+
+        Ok(())
+    }
+}
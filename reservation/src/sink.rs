@@ -0,0 +1,29 @@
+use std::fmt::Debug;
+
+use async_trait::async_trait;
+
+/// an external system mirroring reservations, e.g. a remote inventory.
+///
+/// `reserve_external` runs inside the reserve transaction, after the local insert. If it fails,
+/// or the local transaction fails to commit afterwards, `release_external` is called once to undo
+/// whatever the external side may have recorded.
+#[async_trait]
+pub trait ReservationSink: Debug + Send + Sync {
+    async fn reserve_external(&self, rsvp: &abi::Reservation) -> Result<(), abi::Error>;
+    async fn release_external(&self, rsvp: &abi::Reservation) -> Result<(), abi::Error>;
+}
+
+/// the default sink, for reservations that live only in this database
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopSink;
+
+#[async_trait]
+impl ReservationSink for NoopSink {
+    async fn reserve_external(&self, _rsvp: &abi::Reservation) -> Result<(), abi::Error> {
+        Ok(())
+    }
+
+    async fn release_external(&self, _rsvp: &abi::Reservation) -> Result<(), abi::Error> {
+        Ok(())
+    }
+}
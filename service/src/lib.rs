@@ -0,0 +1,155 @@
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use abi::{
+    reservation_service_server::ReservationService, CancelRequest, CancelResponse, ConfirmRequest,
+    ConfirmResponse, GetRequest, GetResponse, ListenRequest, QueryRequest, ReserveRequest,
+    ReserveResponse, UpdateRequest, UpdateResponse,
+};
+use futures::{Stream, StreamExt};
+use reservation::{ReservationManager, Rsvp};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+pub type ReservationStream = Pin<Box<dyn Stream<Item = Result<abi::Reservation, Status>> + Send>>;
+
+pub struct RsvpService {
+    manager: ReservationManager,
+    active_streams: Arc<AtomicUsize>,
+    streamed_rows: Arc<AtomicUsize>,
+}
+
+impl RsvpService {
+    pub fn new(manager: ReservationManager) -> Self {
+        Self {
+            manager,
+            active_streams: Arc::new(AtomicUsize::new(0)),
+            streamed_rows: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// number of `query` streams still being served
+    pub fn active_streams(&self) -> Arc<AtomicUsize> {
+        self.active_streams.clone()
+    }
+
+    /// total rows handed to `query` clients so far
+    pub fn streamed_rows(&self) -> Arc<AtomicUsize> {
+        self.streamed_rows.clone()
+    }
+}
+
+/// decrements the active stream count once the response stream is dropped,
+/// whether it finished or the client went away.
+struct StreamGuard(Arc<AtomicUsize>);
+
+impl StreamGuard {
+    fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[tonic::async_trait]
+impl ReservationService for RsvpService {
+    async fn reserve(
+        &self,
+        request: Request<ReserveRequest>,
+    ) -> Result<Response<ReserveResponse>, Status> {
+        let Some(rsvp) = request.into_inner().reservation else {
+            return Err(Status::invalid_argument("missing reservation"));
+        };
+        let rsvp = self.manager.reserve(rsvp).await?;
+        Ok(Response::new(ReserveResponse {
+            reservation: Some(rsvp),
+        }))
+    }
+
+    async fn confirm(
+        &self,
+        request: Request<ConfirmRequest>,
+    ) -> Result<Response<ConfirmResponse>, Status> {
+        let rsvp = self
+            .manager
+            .change_status(request.into_inner().id, "")
+            .await?;
+        Ok(Response::new(ConfirmResponse {
+            reservation: Some(rsvp),
+        }))
+    }
+
+    async fn update(
+        &self,
+        request: Request<UpdateRequest>,
+    ) -> Result<Response<UpdateResponse>, Status> {
+        let request = request.into_inner();
+        let Some(rsvp) = request.reservation else {
+            return Err(Status::invalid_argument("missing reservation"));
+        };
+        let rsvp = self.manager.update_note(rsvp.id, request.note).await?;
+        Ok(Response::new(UpdateResponse {
+            reservation: Some(rsvp),
+        }))
+    }
+
+    async fn cancel(
+        &self,
+        request: Request<CancelRequest>,
+    ) -> Result<Response<CancelResponse>, Status> {
+        let rsvp = self.manager.cancel(request.into_inner().id).await?;
+        Ok(Response::new(CancelResponse {
+            reservation: Some(rsvp),
+        }))
+    }
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let rsvp = self.manager.get(request.into_inner().id).await?;
+        Ok(Response::new(GetResponse {
+            reservation: Some(rsvp),
+        }))
+    }
+
+    type queryStream = ReservationStream;
+
+    async fn query(
+        &self,
+        request: Request<QueryRequest>,
+    ) -> Result<Response<Self::queryStream>, Status> {
+        let Some(query) = request.into_inner().query else {
+            return Err(Status::invalid_argument("missing query"));
+        };
+        let rows = self.manager.query_stream(query).await?;
+
+        let guard = StreamGuard::new(self.active_streams.clone());
+        let streamed_rows = self.streamed_rows.clone();
+        // the item type is fixed by the generated trait
+        #[allow(clippy::result_large_err)]
+        let stream = ReceiverStream::new(rows).map(move |row| {
+            let _guard = &guard;
+            streamed_rows.fetch_add(1, Ordering::SeqCst);
+            row.map_err(Status::from)
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    type listenStream = ReservationStream;
+
+    async fn listen(
+        &self,
+        _request: Request<ListenRequest>,
+    ) -> Result<Response<Self::listenStream>, Status> {
+        Err(Status::unimplemented("listen is not supported yet"))
+    }
+}
@@ -1,3 +1,24 @@
-fn main() {
-    println!("Hello, world!");
+use abi::reservation_service_server::ReservationServiceServer;
+use reservation::ReservationManager;
+use reservation_service::RsvpService;
+use sqlx::PgPool;
+use tonic::transport::Server;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let url = std::env::var("DATABASE_URL")?;
+    let addr = std::env::var("RSVP_ADDR").unwrap_or_else(|_| "0.0.0.0:50051".to_string());
+
+    let pool = PgPool::connect(&url).await?;
+    let manager = ReservationManager::new(pool);
+    // refuse to serve a database that would not detect conflicts
+    manager.verify_constraints().await?;
+    let service = RsvpService::new(manager);
+
+    Server::builder()
+        .add_service(ReservationServiceServer::new(service))
+        .serve(addr.parse()?)
+        .await?;
+
+    Ok(())
 }
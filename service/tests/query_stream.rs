@@ -0,0 +1,87 @@
+use std::{sync::atomic::Ordering, time::Duration};
+
+use abi::{
+    reservation_service_client::ReservationServiceClient,
+    reservation_service_server::ReservationServiceServer, QueryRequest, Reservation,
+    ReservationQueryBuilder, ReservationStatus,
+};
+use chrono::{DateTime, FixedOffset};
+use reservation::{ReservationManager, Rsvp};
+use reservation_service::RsvpService;
+use tonic::transport::{Endpoint, Server, Uri};
+use tower::service_fn;
+
+#[sqlx_database_tester::test(pool(variable = "migrated_pool", migrations = "../migrations"))]
+async fn query_should_stop_streaming_when_client_cancels() {
+    let manager = ReservationManager::new(migrated_pool.clone());
+    let first: DateTime<FixedOffset> = "2023-01-01T10:00:00+0000".parse().unwrap();
+    for day in 0..30 {
+        let start = first + chrono::Duration::days(day);
+        let end = start + chrono::Duration::hours(1);
+        manager
+            .reserve(Reservation::new_pending("tyrid", "room-1", start, end, ""))
+            .await
+            .unwrap();
+    }
+
+    let service = RsvpService::new(ReservationManager::new(migrated_pool.clone()));
+    let active_streams = service.active_streams();
+    let streamed_rows = service.streamed_rows();
+
+    // serve over an in-process duplex pipe
+    let (client_io, server_io) = tokio::io::duplex(1024);
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(ReservationServiceServer::new(service))
+            .serve_with_incoming(tokio_stream::once(Ok::<_, std::io::Error>(server_io)))
+            .await
+    });
+    let mut client_io = Some(client_io);
+    let channel = Endpoint::try_from("http://[::]:50051")
+        .unwrap()
+        // a small window so the server cannot run far ahead of the client
+        .initial_stream_window_size(Some(512))
+        .connect_with_connector(service_fn(move |_: Uri| {
+            let io = client_io.take();
+            async move { io.ok_or_else(|| std::io::Error::other("reconnect")) }
+        }))
+        .await
+        .unwrap();
+    let mut client = ReservationServiceClient::new(channel);
+
+    let query = ReservationQueryBuilder::default()
+        .resource_id("room-1")
+        .start(
+            "2022-12-31T00:00:00Z"
+                .parse::<prost_types::Timestamp>()
+                .unwrap(),
+        )
+        .end(
+            "2023-02-28T00:00:00Z"
+                .parse::<prost_types::Timestamp>()
+                .unwrap(),
+        )
+        .status(ReservationStatus::Pending as i32)
+        .pagesize(50)
+        .build()
+        .unwrap();
+    let mut stream = client
+        .query(QueryRequest { query: Some(query) })
+        .await
+        .unwrap()
+        .into_inner();
+    for _ in 0..10 {
+        assert!(stream.message().await.unwrap().is_some());
+    }
+    assert_eq!(active_streams.load(Ordering::SeqCst), 1);
+    drop(stream);
+
+    for _ in 0..50 {
+        if active_streams.load(Ordering::SeqCst) == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(active_streams.load(Ordering::SeqCst), 0);
+    assert!(streamed_rows.load(Ordering::SeqCst) < 30);
+}